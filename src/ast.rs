@@ -1,9 +1,18 @@
 pub use crate::token::*;
+use crate::diagnostic::*;
 pub use std::rc::*;
 pub use std::cell::*;
 
 pub type ParentData = (Option<Weak<RefCell<AstFile>>>, Option<Weak<RefCell<AstScope>>>);
 
+/// The argument given for each parameter of a procedure, `None` where the parameter's default value is used,
+/// see `AstCall::bind_arguments`
+pub type BoundArguments = Vec<Option<Rc<RefCell<AstExpression>>>>;
+
+/// The value given for each field of a struct, `None` where the field's default value is used,
+/// see `AstStructLiteral::bind_fields`
+pub type BoundFields = Vec<Option<Rc<RefCell<AstExpression>>>>;
+
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub enum Ast {
@@ -14,8 +23,7 @@ pub enum Ast {
 #[derive(Clone, Debug)]
 pub struct AstFile {
     pub parent_data: ParentData,
-    pub file_path: String,
-    pub source: String,
+    pub file_id: FileId,
     pub scope: Rc<RefCell<AstScope>>,
 }
 
@@ -25,12 +33,136 @@ pub enum AstStatement {
     Scope(Rc<RefCell<AstScope>>),
     Declaration(Rc<RefCell<AstDeclaration>>),
     Assignment(Rc<RefCell<AstAssignment>>),
+    If(Rc<RefCell<AstIf>>),
+    Match(Rc<RefCell<AstMatch>>),
+    While(Rc<RefCell<AstWhile>>),
+    Loop(Rc<RefCell<AstLoop>>),
+    For(Rc<RefCell<AstFor>>),
+    Break(Rc<RefCell<AstBreak>>),
+    Continue(Rc<RefCell<AstContinue>>),
+    Return(Rc<RefCell<AstReturn>>),
+    Error,
+}
+
+impl AstStatement {
+    /// Whether control never continues after the statement, because every path through it
+    /// returns, breaks out, continues or loops forever
+    pub fn diverges(&self) -> bool {
+        match self {
+            AstStatement::Return(_) |
+            AstStatement::Break(_) |
+            AstStatement::Continue(_) => true,
+            AstStatement::Scope(scope) => scope.borrow().diverges(),
+            AstStatement::If(if_) => if_.borrow().diverges(),
+            AstStatement::Match(match_) => match_.borrow().diverges(),
+            AstStatement::Loop(loop_) => !loop_.borrow().has_break(),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct AstScope {
     pub parent_data: ParentData,
     pub statements: Vec<Rc<RefCell<AstStatement>>>,
+    /// The expression at the end of the scope without a ';', which the scope evaluates to
+    pub value: Option<Rc<RefCell<AstExpression>>>,
+    /// What the scope is the body of, if anything
+    pub owner: Option<AstScopeOwner>,
+}
+
+#[derive(Clone, Debug)]
+pub enum AstScopeOwner {
+    Procedure(Weak<RefCell<AstProcedure>>),
+    While(Weak<RefCell<AstWhile>>),
+    Loop(Weak<RefCell<AstLoop>>),
+    For(Weak<RefCell<AstFor>>),
+}
+
+impl AstScopeOwner {
+    /// The label of the loop this scope is the body of, `None` for unlabeled loops and procedures
+    pub fn label(&self) -> Option<Token> {
+        match self {
+            AstScopeOwner::Procedure(_) => Option::None,
+            AstScopeOwner::While(while_) => while_.upgrade()?.borrow().label.clone(),
+            AstScopeOwner::Loop(loop_) => loop_.upgrade()?.borrow().label.clone(),
+            AstScopeOwner::For(for_) => for_.upgrade()?.borrow().label.clone(),
+        }
+    }
+}
+
+impl AstScope {
+    /// Whether control never reaches the end of the scope
+    pub fn diverges(&self) -> bool {
+        self.statements.iter().any(|statement| statement.borrow().diverges())
+    }
+
+    /// Whether the scope contains a 'break' out of the loop with the label `loop_label`,
+    /// `nested` is set once the search is inside another loop, where only labeled breaks can reach
+    fn breaks_out_of(&self, loop_label: Option<&Token>, nested: bool) -> bool {
+        let breaks_out_of_if = |if_: &Rc<RefCell<AstIf>>| {
+            let mut if_ = if_.clone();
+            loop {
+                if if_.borrow().then_scope.borrow().breaks_out_of(loop_label, nested) {
+                    return true;
+                }
+                let else_ = if_.borrow().else_.clone();
+                match else_ {
+                    Option::Some(AstElse::If(else_if)) => if_ = else_if,
+                    Option::Some(AstElse::Scope(scope)) => return scope.borrow().breaks_out_of(loop_label, nested),
+                    Option::None => return false,
+                }
+            }
+        };
+
+        let in_statements = self.statements.iter().any(|statement| match &*statement.borrow() {
+            AstStatement::Break(break_) => match &break_.borrow().label {
+                Option::Some(label) => loop_label.is_some_and(|loop_label| loop_label.kind == label.kind),
+                Option::None => !nested,
+            },
+            AstStatement::Scope(scope) => scope.borrow().breaks_out_of(loop_label, nested),
+            AstStatement::If(if_) => breaks_out_of_if(if_),
+            AstStatement::Match(match_) => match_.borrow().arms.iter().any(|arm| arm.body.borrow().breaks_out_of(loop_label, nested)),
+            AstStatement::While(while_) => while_.borrow().body.borrow().breaks_out_of(loop_label, true),
+            AstStatement::Loop(loop_) => loop_.borrow().body.borrow().breaks_out_of(loop_label, true),
+            AstStatement::For(for_) => for_.borrow().body.borrow().breaks_out_of(loop_label, true),
+            _ => false,
+        });
+
+        in_statements || match &self.value {
+            Option::Some(value) => match &*value.borrow() {
+                AstExpression::If(if_) => breaks_out_of_if(if_),
+                AstExpression::Match(match_) => match_.borrow().arms.iter().any(|arm| arm.body.borrow().breaks_out_of(loop_label, nested)),
+                _ => false,
+            },
+            Option::None => false,
+        }
+    }
+
+    /// Walks up the scope tree from `scope` to the innermost loop with the given label (or any loop
+    /// without one) that is inside the same procedure
+    pub fn enclosing_loop(scope: &Rc<RefCell<AstScope>>, label: Option<&Token>) -> Option<AstScopeOwner> {
+        let mut scope = scope.clone();
+        loop {
+            let owner = scope.borrow().owner.clone();
+            match owner {
+                Option::Some(AstScopeOwner::Procedure(_)) => return Option::None,
+                Option::Some(owner) => {
+                    let matches = match label {
+                        Option::Some(label) => owner.label().is_some_and(|owner_label| owner_label.kind == label.kind),
+                        Option::None => true,
+                    };
+                    if matches {
+                        return Option::Some(owner);
+                    }
+                }
+                Option::None => {}
+            }
+
+            let parent = scope.borrow().parent_data.1.as_ref().and_then(|parent| parent.upgrade())?;
+            scope = parent;
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -40,6 +172,7 @@ pub struct AstDeclaration {
     pub type_: Rc<RefCell<Option<AstType>>>,
     pub value: Rc<RefCell<Option<AstExpression>>>,
     pub constant: bool,
+    pub doc_comments: Vec<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -53,24 +186,500 @@ pub struct AstAssignment {
 #[derive(Clone, Debug)]
 pub enum AstExpression {
     Procedure(Rc<RefCell<AstProcedure>>),
+    Call(Rc<RefCell<AstCall>>),
+    Struct(Rc<RefCell<AstStruct>>),
+    StructLiteral(Rc<RefCell<AstStructLiteral>>),
+    Field(Rc<RefCell<AstField>>),
+    ArrayLiteral(Rc<RefCell<AstArrayLiteral>>),
+    Index(Rc<RefCell<AstIndex>>),
+    Slice(Rc<RefCell<AstSlice>>),
+    Enum(Rc<RefCell<AstEnum>>),
+    If(Rc<RefCell<AstIf>>),
+    Match(Rc<RefCell<AstMatch>>),
     Name(Rc<RefCell<AstName>>),
     Literal(Rc<RefCell<AstLiteral>>),
     Unary(Rc<RefCell<AstUnary>>),
     Binary(Rc<RefCell<AstBinary>>),
+    Error(Span),
+}
+
+impl AstExpression {
+    pub fn span(&self) -> Span {
+        match self {
+            AstExpression::Procedure(procedure) => procedure.borrow().span,
+            AstExpression::Call(call) => call.borrow().span,
+            AstExpression::Struct(struct_) => struct_.borrow().span,
+            AstExpression::StructLiteral(literal) => literal.borrow().span,
+            AstExpression::Field(field) => field.borrow().span,
+            AstExpression::ArrayLiteral(array) => array.borrow().span,
+            AstExpression::Index(index) => index.borrow().span,
+            AstExpression::Slice(slice) => slice.borrow().span,
+            AstExpression::Enum(enum_) => enum_.borrow().span,
+            AstExpression::If(if_) => if_.borrow().span,
+            AstExpression::Match(match_) => match_.borrow().span,
+            AstExpression::Name(name) => name.borrow().token.span,
+            AstExpression::Literal(literal) => literal.borrow().token.span,
+            AstExpression::Unary(unary) => {
+                let (operator, operand) = (unary.borrow().operator.span, unary.borrow().operand.clone());
+                let span = operator.to(operand.borrow().span());
+                span
+            }
+            AstExpression::Binary(binary) => {
+                let (left, right) = (binary.borrow().left.clone(), binary.borrow().right.clone());
+                let span = left.borrow().span().to(right.borrow().span());
+                span
+            }
+            AstExpression::Error(span) => *span,
+        }
+    }
+
+    /// Whether the expression refers to a storage location that can be assigned to
+    pub fn is_place(&self) -> bool {
+        match self {
+            AstExpression::Name(_) => true,
+            AstExpression::Field(field) => field.borrow().operand.borrow().is_place(),
+            AstExpression::Index(index) => index.borrow().operand.borrow().is_place(),
+            AstExpression::Unary(unary) => unary.borrow().operator.kind == TokenKind::Caret,
+            _ => false,
+        }
+    }
+
+    /// Whether the expression indexes a temporary, e.g. `f()[0]` or `f()[0].x`,
+    /// which is still a place when the temporary is a slice, something only the checker knows
+    pub fn indexes_temporary(&self) -> bool {
+        match self {
+            AstExpression::Field(field) => field.borrow().operand.borrow().indexes_temporary(),
+            AstExpression::Index(index) => !index.borrow().operand.borrow().is_place(),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct AstProcedure {
     pub parent_data: ParentData,
+    pub span: Span,
     pub arguments: Vec<Rc<RefCell<AstDeclaration>>>,
     pub return_type: Rc<RefCell<Option<AstType>>>,
     pub scope: Rc<RefCell<AstScope>>,
 }
 
+#[derive(Clone, Debug)]
+pub struct AstCall {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub callee: Rc<RefCell<AstExpression>>,
+    pub arguments: Vec<AstArgument>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AstArgument {
+    pub name: Option<Token>,
+    pub value: Rc<RefCell<AstExpression>>,
+}
+
+impl AstArgument {
+    pub fn span(&self) -> Span {
+        let value = self.value.borrow().span();
+        match &self.name {
+            Option::Some(name) => name.span.to(value),
+            Option::None => value,
+        }
+    }
+}
+
+impl AstCall {
+    /// Matches the arguments of the call to the parameters of `procedure`, positional arguments
+    /// fill the parameters in order and named arguments fill the parameter with the same name.
+    /// Parameters with a default value may be left out.
+    pub fn bind_arguments(&self, procedure: &AstProcedure) -> Result<BoundArguments, Vec<Diagnostic>> {
+        let mut bound = vec![Option::None; procedure.arguments.len()];
+        let mut diagnostics = Vec::new();
+        let mut next_positional = 0;
+
+        for argument in &self.arguments {
+            let index = if let Option::Some(name) = &argument.name {
+                match procedure.arguments.iter().position(|parameter| parameter.borrow().name.kind == name.kind) {
+                    Option::Some(index) => index,
+                    Option::None => {
+                        diagnostics.push(Diagnostic::error(
+                            format!("Procedure has no parameter named '{}'", name.identifier()),
+                            name.span,
+                        ));
+                        continue;
+                    }
+                }
+            } else {
+                next_positional += 1;
+                if next_positional > procedure.arguments.len() {
+                    diagnostics.push(Diagnostic::error(
+                        format!("Expected at most {} arguments", procedure.arguments.len()),
+                        argument.span(),
+                    ));
+                    continue;
+                }
+                next_positional - 1
+            };
+
+            if bound[index].is_some() {
+                let parameter = procedure.arguments[index].borrow();
+                diagnostics.push(
+                    Diagnostic::error(format!("Parameter '{}' is given more than once", parameter.name.identifier()), argument.span())
+                        .with_label(parameter.name.span, String::from("parameter declared here"))
+                );
+                continue;
+            }
+            bound[index] = Option::Some(argument.value.clone());
+        }
+
+        for (parameter, argument) in procedure.arguments.iter().zip(&bound) {
+            let parameter = parameter.borrow();
+            if argument.is_none() && parameter.value.borrow().is_none() {
+                diagnostics.push(
+                    Diagnostic::error(format!("Missing argument for parameter '{}'", parameter.name.identifier()), self.span)
+                        .with_label(parameter.name.span, String::from("parameter declared here"))
+                );
+            }
+        }
+
+        if diagnostics.is_empty() {
+            Result::Ok(bound)
+        } else {
+            Result::Err(diagnostics)
+        }
+    }
+}
+
+/// `struct { name: type, name: type = default }`
+#[derive(Clone, Debug)]
+pub struct AstStruct {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub fields: Vec<Rc<RefCell<AstDeclaration>>>,
+}
+
+/// `Name{ field = value, field = value }`
+#[derive(Clone, Debug)]
+pub struct AstStructLiteral {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub type_: Rc<RefCell<AstType>>,
+    pub fields: Vec<AstFieldValue>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AstFieldValue {
+    pub name: Token,
+    pub value: Rc<RefCell<AstExpression>>,
+}
+
+impl AstStructLiteral {
+    /// Matches the values of the literal to the fields of `struct_` by name,
+    /// fields with a default value may be left out
+    pub fn bind_fields(&self, struct_: &AstStruct) -> Result<BoundFields, Vec<Diagnostic>> {
+        let mut bound = vec![Option::None; struct_.fields.len()];
+        let mut diagnostics = Vec::new();
+
+        for field_value in &self.fields {
+            match struct_.fields.iter().position(|field| field.borrow().name.kind == field_value.name.kind) {
+                Option::Some(index) => bound[index] = Option::Some(field_value.value.clone()),
+                Option::None => {
+                    diagnostics.push(Diagnostic::error(
+                        format!("Struct has no field named '{}'", field_value.name.identifier()),
+                        field_value.name.span,
+                    ));
+                }
+            }
+        }
+
+        for (field, value) in struct_.fields.iter().zip(&bound) {
+            let field = field.borrow();
+            if value.is_none() && field.value.borrow().is_none() {
+                diagnostics.push(
+                    Diagnostic::error(format!("Missing value for field '{}'", field.name.identifier()), self.span)
+                        .with_label(field.name.span, String::from("field declared here"))
+                );
+            }
+        }
+
+        if diagnostics.is_empty() {
+            Result::Ok(bound)
+        } else {
+            Result::Err(diagnostics)
+        }
+    }
+}
+
+/// `operand.name`
+#[derive(Clone, Debug)]
+pub struct AstField {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub operand: Rc<RefCell<AstExpression>>,
+    pub name: Token,
+}
+
+/// `[a, b, c]`
+#[derive(Clone, Debug)]
+pub struct AstArrayLiteral {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub elements: Vec<Rc<RefCell<AstExpression>>>,
+}
+
+/// `operand[index]`
+#[derive(Clone, Debug)]
+pub struct AstIndex {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub operand: Rc<RefCell<AstExpression>>,
+    pub index: Rc<RefCell<AstExpression>>,
+}
+
+/// `operand[start..end]`, a missing bound means the start or the end of `operand`
+#[derive(Clone, Debug)]
+pub struct AstSlice {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub operand: Rc<RefCell<AstExpression>>,
+    pub start: Option<Rc<RefCell<AstExpression>>>,
+    pub end: Option<Rc<RefCell<AstExpression>>>,
+}
+
+/// `enum { Name, Name(type, type) }`
+#[derive(Clone, Debug)]
+pub struct AstEnum {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub variants: Vec<AstVariant>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AstVariant {
+    pub name: Token,
+    /// The types of the values the variant carries, empty for a plain variant
+    pub payload: Vec<Rc<RefCell<AstType>>>,
+    pub doc_comments: Vec<String>,
+}
+
+/// `match value { pattern => expression, pattern => { ... } }`
+#[derive(Clone, Debug)]
+pub struct AstMatch {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub value: Rc<RefCell<AstExpression>>,
+    pub arms: Vec<AstMatchArm>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AstMatchArm {
+    pub pattern: AstPattern,
+    /// An arm written as `pattern => expression` gets a scope with just that expression as its value,
+    /// the bindings of the pattern are declared in this scope
+    pub body: Rc<RefCell<AstScope>>,
+}
+
+#[derive(Clone, Debug)]
+pub enum AstPattern {
+    /// `_`, matches anything
+    Wildcard(Token),
+    /// `Name` or `Name(a, b)`
+    Variant(Rc<RefCell<AstVariantPattern>>),
+    Literal(Rc<RefCell<AstLiteral>>),
+}
+
+impl AstPattern {
+    pub fn span(&self) -> Span {
+        match self {
+            AstPattern::Wildcard(token) => token.span,
+            AstPattern::Variant(variant) => variant.borrow().span,
+            AstPattern::Literal(literal) => literal.borrow().token.span,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AstVariantPattern {
+    pub span: Span,
+    pub name: Token,
+    /// One declaration per payload value, `None` for a `_` that ignores the value
+    pub bindings: Vec<Option<Rc<RefCell<AstDeclaration>>>>,
+}
+
+impl AstMatch {
+    /// Whether the match produces a value, i.e. every arm ends with a value
+    pub fn yields_value(&self) -> bool {
+        !self.arms.is_empty() && self.arms.iter().all(|arm| arm.body.borrow().value.is_some())
+    }
+
+    /// Whether control never continues after any of the arms
+    pub fn diverges(&self) -> bool {
+        !self.arms.is_empty() && self.arms.iter().all(|arm| arm.body.borrow().diverges())
+    }
+
+    /// Checks the patterns against the variants of `enum_`, the type of the matched value:
+    /// every pattern has to name a variant with a binding for each payload value,
+    /// and every variant has to be matched by some arm
+    pub fn check_variants(&self, enum_: &AstEnum) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut matched = vec![false; enum_.variants.len()];
+        let mut has_wildcard = false;
+
+        for arm in &self.arms {
+            match &arm.pattern {
+                AstPattern::Wildcard(_) => has_wildcard = true,
+
+                AstPattern::Variant(pattern) => {
+                    let pattern = pattern.borrow();
+                    let index = match enum_.variants.iter().position(|variant| variant.name.kind == pattern.name.kind) {
+                        Option::Some(index) => index,
+                        Option::None => {
+                            diagnostics.push(Diagnostic::error(
+                                format!("Enum has no variant named '{}'", pattern.name.identifier()),
+                                pattern.name.span,
+                            ));
+                            continue;
+                        }
+                    };
+
+                    let variant = &enum_.variants[index];
+                    if variant.payload.len() != pattern.bindings.len() {
+                        diagnostics.push(
+                            Diagnostic::error(
+                                format!("Variant '{}' carries {} values but the pattern binds {}", variant.name.identifier(), variant.payload.len(), pattern.bindings.len()),
+                                pattern.span,
+                            )
+                                .with_label(variant.name.span, String::from("variant declared here"))
+                        );
+                    }
+                    matched[index] = true;
+                }
+
+                AstPattern::Literal(literal) => {
+                    diagnostics.push(Diagnostic::error(String::from("Expected a variant pattern"), literal.borrow().token.span));
+                }
+            }
+        }
+
+        if !has_wildcard {
+            let missing: Vec<_> = enum_.variants.iter()
+                .zip(&matched)
+                .filter(|(_, matched)| !**matched)
+                .map(|(variant, _)| format!("'{}'", variant.name.identifier()))
+                .collect();
+
+            if !missing.is_empty() {
+                diagnostics.push(
+                    Diagnostic::error(String::from("Match does not cover every variant"), self.span)
+                        .with_note(format!("not matched: {}", missing.join(", ")))
+                        .with_help(String::from("add an arm for each of them or a '_' arm"))
+                );
+            }
+        }
+
+        diagnostics
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AstIf {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub condition: Rc<RefCell<AstExpression>>,
+    pub then_scope: Rc<RefCell<AstScope>>,
+    pub else_: Option<AstElse>,
+}
+
+#[derive(Clone, Debug)]
+pub enum AstElse {
+    If(Rc<RefCell<AstIf>>),
+    Scope(Rc<RefCell<AstScope>>),
+}
+
+impl AstIf {
+    /// Whether the `if` has an `else` and control never continues after any of its branches
+    pub fn diverges(&self) -> bool {
+        self.then_scope.borrow().diverges() && match &self.else_ {
+            Option::Some(AstElse::If(if_)) => if_.borrow().diverges(),
+            Option::Some(AstElse::Scope(scope)) => scope.borrow().diverges(),
+            Option::None => false,
+        }
+    }
+
+    /// Whether the `if` produces a value, i.e. it has an `else` and every branch ends with a value
+    pub fn yields_value(&self) -> bool {
+        self.then_scope.borrow().value.is_some() && match &self.else_ {
+            Option::Some(AstElse::If(if_)) => if_.borrow().yields_value(),
+            Option::Some(AstElse::Scope(scope)) => scope.borrow().value.is_some(),
+            Option::None => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AstWhile {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub label: Option<Token>,
+    pub condition: Rc<RefCell<AstExpression>>,
+    pub body: Rc<RefCell<AstScope>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AstLoop {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub label: Option<Token>,
+    pub body: Rc<RefCell<AstScope>>,
+}
+
+/// `for variable in start..end { }`, counting from `start` up to but not including `end`
+#[derive(Clone, Debug)]
+pub struct AstFor {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub label: Option<Token>,
+    /// Declared in `body`
+    pub variable: Rc<RefCell<AstDeclaration>>,
+    pub start: Rc<RefCell<AstExpression>>,
+    pub end: Rc<RefCell<AstExpression>>,
+    pub body: Rc<RefCell<AstScope>>,
+}
+
+impl AstLoop {
+    /// Whether anything breaks out of the loop, without a 'break' it never ends
+    pub fn has_break(&self) -> bool {
+        self.body.borrow().breaks_out_of(self.label.as_ref(), false)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AstBreak {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub label: Option<Token>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AstContinue {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub label: Option<Token>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AstReturn {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub value: Option<Rc<RefCell<AstExpression>>>,
+}
+
 #[derive(Clone, Debug)]
 pub struct AstName {
     pub parent_data: ParentData,
     pub token: Token,
+    /// Set by the resolver, stays `None` for built-in names
+    pub declaration: Option<Weak<RefCell<AstDeclaration>>>,
 }
 
 #[derive(Clone, Debug)]
@@ -79,6 +688,8 @@ pub struct AstLiteral {
     pub token: Token,
 }
 
+/// `^` is a dereference, written either before (`^p`) or after (`p^`) the operand, `&` takes the address of a place.
+/// A postfix `^` has to touch its operand and must not touch an operand after it, otherwise it is xor
 #[derive(Clone, Debug)]
 pub struct AstUnary {
     pub parent_data: ParentData,
@@ -97,4 +708,75 @@ pub struct AstBinary {
 #[derive(Clone, Debug)]
 pub enum AstType {
     Name(Rc<RefCell<AstName>>),
+    Pointer(Rc<RefCell<AstPointerType>>),
+    Array(Rc<RefCell<AstArrayType>>),
+    Slice(Rc<RefCell<AstSliceType>>),
+    Procedure(Rc<RefCell<AstProcedureType>>),
+    Optional(Rc<RefCell<AstOptionalType>>),
+}
+
+impl AstType {
+    pub fn span(&self) -> Span {
+        match self {
+            AstType::Name(name) => name.borrow().token.span,
+            AstType::Pointer(pointer) => pointer.borrow().span,
+            AstType::Array(array) => array.borrow().span,
+            AstType::Slice(slice) => slice.borrow().span,
+            AstType::Procedure(procedure) => procedure.borrow().span,
+            AstType::Optional(optional) => optional.borrow().span,
+        }
+    }
+
+    /// Whether this is the built-in `void` type, i.e. no value at all
+    pub fn is_void(&self) -> bool {
+        match self {
+            AstType::Name(name) => {
+                let is_void = name.borrow().token.kind == TokenKind::Identifier(String::from("void"));
+                is_void
+            }
+            _ => false,
+        }
+    }
+}
+
+/// `^T`
+#[derive(Clone, Debug)]
+pub struct AstPointerType {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub pointee: Rc<RefCell<AstType>>,
+}
+
+/// `[N]T`, where `N` has to be a constant
+#[derive(Clone, Debug)]
+pub struct AstArrayType {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub length: Rc<RefCell<AstExpression>>,
+    pub element: Rc<RefCell<AstType>>,
+}
+
+/// `[]T`
+#[derive(Clone, Debug)]
+pub struct AstSliceType {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub element: Rc<RefCell<AstType>>,
+}
+
+/// `(T, U) -> R`, without a return type the procedure returns nothing
+#[derive(Clone, Debug)]
+pub struct AstProcedureType {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub parameters: Vec<Rc<RefCell<AstType>>>,
+    pub return_type: Rc<RefCell<Option<AstType>>>,
+}
+
+/// `?T`, either a `T` or nothing
+#[derive(Clone, Debug)]
+pub struct AstOptionalType {
+    pub parent_data: ParentData,
+    pub span: Span,
+    pub inner: Rc<RefCell<AstType>>,
 }
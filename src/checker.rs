@@ -0,0 +1,1517 @@
+pub use crate::ast::*;
+pub use crate::diagnostic::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub enum Type {
+    /// The type of something that already failed to check, it is compatible with everything
+    /// so that a single mistake is only reported once
+    Error,
+    Void,
+    Bool,
+    Char,
+    String,
+    /// `int` is `i64` and `float` is `f64`
+    Number(NumberSuffix),
+    Pointer(Box<Type>),
+    Array(u64, Box<Type>),
+    Slice(Box<Type>),
+    Procedure(Vec<Type>, Box<Type>),
+    Optional(Box<Type>),
+    /// A struct type, identified by the constant that declares it
+    Struct(Rc<RefCell<AstDeclaration>>),
+    /// An enum type, identified by the constant that declares it
+    Enum(Rc<RefCell<AstDeclaration>>),
+    /// The type of a name that refers to a type, e.g. `Vec2` in `v := Vec2{ x = 1.0, y = 2.0 };`
+    Type(Box<Type>),
+}
+
+impl PartialEq for Type {
+    fn eq(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Error, Type::Error) |
+            (Type::Void, Type::Void) |
+            (Type::Bool, Type::Bool) |
+            (Type::Char, Type::Char) |
+            (Type::String, Type::String) => true,
+            (Type::Number(a), Type::Number(b)) => a == b,
+            (Type::Pointer(a), Type::Pointer(b)) |
+            (Type::Slice(a), Type::Slice(b)) |
+            (Type::Optional(a), Type::Optional(b)) |
+            (Type::Type(a), Type::Type(b)) => a == b,
+            (Type::Array(a_length, a), Type::Array(b_length, b)) => a_length == b_length && a == b,
+            (Type::Procedure(a_parameters, a), Type::Procedure(b_parameters, b)) => a_parameters == b_parameters && a == b,
+            (Type::Struct(a), Type::Struct(b)) |
+            (Type::Enum(a), Type::Enum(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Type {
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Type::Number(number) if !number.is_float())
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Type::Number(_))
+    }
+
+    /// Whether a value of this type can be stored where a `to` is expected
+    pub fn is_assignable_to(&self, to: &Type) -> bool {
+        match (self, to) {
+            (Type::Error, _) | (_, Type::Error) => true,
+            (_, Type::Optional(inner)) if self.is_assignable_to(inner) => true,
+            _ => self == to,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Error => write!(f, "{{error}}"),
+            Type::Void => write!(f, "void"),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::String => write!(f, "string"),
+            Type::Number(NumberSuffix::I64) => write!(f, "int"),
+            Type::Number(NumberSuffix::F64) => write!(f, "float"),
+            Type::Number(number) => write!(f, "{}", number),
+            Type::Pointer(pointee) => write!(f, "^{}", pointee),
+            Type::Array(length, element) => write!(f, "[{}]{}", length, element),
+            Type::Slice(element) => write!(f, "[]{}", element),
+            Type::Procedure(parameters, return_type) => {
+                let parameters: Vec<_> = parameters.iter().map(Type::to_string).collect();
+                write!(f, "({})", parameters.join(", "))?;
+                if **return_type != Type::Void {
+                    write!(f, " -> {}", return_type)?;
+                }
+                Result::Ok(())
+            }
+            Type::Optional(inner) => write!(f, "?{}", inner),
+            Type::Struct(declaration) |
+            Type::Enum(declaration) => write!(f, "{}", declaration.borrow().name.identifier()),
+            Type::Type(_) => write!(f, "type"),
+        }
+    }
+}
+
+/// The type a built-in type name stands for
+pub fn builtin_type(name: &str) -> Option<Type> {
+    match name {
+        "void" => Option::Some(Type::Void),
+        "bool" => Option::Some(Type::Bool),
+        "char" => Option::Some(Type::Char),
+        "string" => Option::Some(Type::String),
+        "int" => Option::Some(Type::Number(NumberSuffix::I64)),
+        "float" => Option::Some(Type::Number(NumberSuffix::F64)),
+        _ => NumberSuffix::from_name(name).map(Type::Number),
+    }
+}
+
+/// Identifies an expression node for `Checker::type_of_expression`
+fn expression_key(expression: &AstExpression) -> Option<*const ()> {
+    Option::Some(match expression {
+        AstExpression::Procedure(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Call(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Struct(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::StructLiteral(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Field(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::ArrayLiteral(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Index(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Slice(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Enum(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::If(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Match(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Name(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Literal(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Unary(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Binary(node) => Rc::as_ptr(node) as *const (),
+        AstExpression::Error(_) => return Option::None,
+    })
+}
+
+/// The procedure a constant declares, if it declares one
+pub(crate) fn declared_procedure(declaration: &Rc<RefCell<AstDeclaration>>) -> Option<Rc<RefCell<AstProcedure>>> {
+    let declaration = declaration.borrow();
+    let procedure = match &*declaration.value.borrow() {
+        Option::Some(AstExpression::Procedure(procedure)) if declaration.constant => Option::Some(procedure.clone()),
+        _ => Option::None,
+    };
+    procedure
+}
+
+pub(crate) fn declared_struct(declaration: &Rc<RefCell<AstDeclaration>>) -> Option<Rc<RefCell<AstStruct>>> {
+    match &*declaration.borrow().value.borrow() {
+        Option::Some(AstExpression::Struct(struct_)) => Option::Some(struct_.clone()),
+        _ => Option::None,
+    }
+}
+
+pub(crate) fn declared_enum(declaration: &Rc<RefCell<AstDeclaration>>) -> Option<Rc<RefCell<AstEnum>>> {
+    match &*declaration.borrow().value.borrow() {
+        Option::Some(AstExpression::Enum(enum_)) => Option::Some(enum_.clone()),
+        _ => Option::None,
+    }
+}
+
+/// For a number literal without a suffix (possibly negated), which takes its type from where it is used,
+/// whether it is a float literal
+fn untyped_literal(expression: &AstExpression) -> Option<bool> {
+    match expression {
+        AstExpression::Literal(literal) => match literal.borrow().token.kind {
+            TokenKind::Integer(_, Option::None) => Option::Some(false),
+            TokenKind::Float(_, Option::None) => Option::Some(true),
+            _ => Option::None,
+        },
+        AstExpression::Unary(unary) => {
+            let unary = unary.borrow();
+            if matches!(unary.operator.kind, TokenKind::Plus | TokenKind::Minus) {
+                untyped_literal(&unary.operand.borrow())
+            } else {
+                Option::None
+            }
+        }
+        _ => Option::None,
+    }
+}
+
+/// Works out the type of every declaration and expression after name resolution, and reports
+/// everything that doesn't fit together. Declarations are checked on demand, so constants can be used
+/// before their definition, and the results are kept so later passes can look them up.
+#[derive(Default)]
+pub struct Checker {
+    declaration_types: HashMap<*const RefCell<AstDeclaration>, Type>,
+    expression_types: HashMap<*const (), Type>,
+    resolved_types: HashMap<*const AstType, Type>,
+    /// Declarations whose type is being worked out, to catch ones that depend on themselves
+    in_progress: Vec<*const RefCell<AstDeclaration>>,
+    /// The return type of each procedure around the code being checked, innermost last
+    return_types: Vec<Type>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    pub fn new() -> Checker {
+        Checker::default()
+    }
+
+    pub fn check(&mut self, ast: &Rc<RefCell<Ast>>) -> Result<(), Vec<Diagnostic>> {
+        match &*ast.borrow() {
+            Ast::File(file) => {
+                self.check_scope(&file.borrow().scope.borrow(), Option::None);
+            }
+            Ast::Statement(statement) => self.check_statement(&statement.borrow()),
+        }
+
+        let diagnostics = std::mem::take(&mut self.diagnostics);
+        if diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+            Result::Err(diagnostics)
+        } else {
+            Result::Ok(())
+        }
+    }
+
+    /// The type of a declaration that has been checked
+    pub fn type_of_declaration(&self, declaration: &Rc<RefCell<AstDeclaration>>) -> Option<&Type> {
+        self.declaration_types.get(&Rc::as_ptr(declaration))
+    }
+
+    /// The type of an expression that has been checked
+    pub fn type_of_expression(&self, expression: &AstExpression) -> Option<&Type> {
+        self.expression_types.get(&expression_key(expression)?)
+    }
+
+    /// The type an `AstType` written in the source stands for, once it has been checked
+    pub fn type_of_type(&self, type_: &AstType) -> Option<&Type> {
+        self.resolved_types.get(&(type_ as *const AstType))
+    }
+
+    /// `AstExpression::is_place` for a checked expression, which also knows that
+    /// indexing a temporary slice refers to the storage the slice points to
+    fn is_place(&self, expression: &AstExpression) -> bool {
+        match expression {
+            AstExpression::Field(field) => self.is_place(&field.borrow().operand.borrow()),
+            AstExpression::Index(index) => {
+                let operand = index.borrow().operand.clone();
+                let operand = operand.borrow();
+                matches!(self.type_of_expression(&operand), Option::Some(Type::Slice(_) | Type::Error)) || self.is_place(&operand)
+            }
+            _ => expression.is_place(),
+        }
+    }
+
+    /// The name whose storage an assignment to the place changes,
+    /// `None` when the place is reached through a pointer or a slice
+    fn assigned_name(&self, place: &AstExpression) -> Option<Rc<RefCell<AstName>>> {
+        match place {
+            AstExpression::Name(name) => Option::Some(name.clone()),
+            AstExpression::Field(field) => self.assigned_name(&field.borrow().operand.borrow()),
+            AstExpression::Index(index) => {
+                let operand = index.borrow().operand.clone();
+                let operand = operand.borrow();
+                match self.type_of_expression(&operand) {
+                    Option::Some(Type::Slice(_)) => Option::None,
+                    _ => self.assigned_name(&operand),
+                }
+            }
+            _ => Option::None,
+        }
+    }
+
+    /// Whether a value of `type_` stores a value of the struct or enum `target` inline, which would make it infinitely large.
+    /// Only looks at types that have already been checked, so a cycle is found once its last member is checked
+    fn contains_by_value(&self, type_: &Type, target: &Rc<RefCell<AstDeclaration>>, visited: &mut Vec<*const RefCell<AstDeclaration>>) -> bool {
+        match type_ {
+            Type::Struct(declaration) | Type::Enum(declaration) => {
+                if Rc::ptr_eq(declaration, target) {
+                    return true;
+                }
+                if visited.contains(&Rc::as_ptr(declaration)) {
+                    return false;
+                }
+                visited.push(Rc::as_ptr(declaration));
+
+                let members: Vec<Type> = if let Option::Some(struct_) = declared_struct(declaration) {
+                    struct_.borrow().fields.iter()
+                        .filter_map(|field| self.type_of_declaration(field).cloned())
+                        .collect()
+                } else if let Option::Some(enum_) = declared_enum(declaration) {
+                    enum_.borrow().variants.iter()
+                        .flat_map(|variant| variant.payload.iter())
+                        .filter_map(|payload| self.type_of_type(&payload.borrow()).cloned())
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                members.iter().any(|member| self.contains_by_value(member, target, visited))
+            }
+            Type::Array(_, element) => self.contains_by_value(element, target, visited),
+            Type::Optional(inner) => self.contains_by_value(inner, target, visited),
+            _ => false,
+        }
+    }
+
+    /// Reports an error if `found` can't be used where `expected` is required
+    fn expect_type(&mut self, found: &Type, expected: &Type, span: Span) {
+        if !found.is_assignable_to(expected) {
+            self.diagnostics.push(Diagnostic::error(format!("Expected {} got {}", expected, found), span));
+        }
+    }
+
+    fn check_condition(&mut self, condition: &AstExpression) {
+        let type_ = self.check_expression(condition, Option::Some(&Type::Bool));
+        self.expect_type(&type_, &Type::Bool, condition.span());
+    }
+
+    fn check_declaration(&mut self, declaration: &Rc<RefCell<AstDeclaration>>) -> Type {
+        let key = Rc::as_ptr(declaration);
+        if let Option::Some(type_) = self.declaration_types.get(&key) {
+            return type_.clone();
+        }
+
+        let (name, type_, value, constant) = {
+            let declaration = declaration.borrow();
+            (declaration.name.clone(), declaration.type_.clone(), declaration.value.clone(), declaration.constant)
+        };
+
+        if self.in_progress.contains(&key) {
+            self.diagnostics.push(Diagnostic::error(format!("The type of '{}' depends on itself", name.identifier()), name.span));
+            self.declaration_types.insert(key, Type::Error);
+            return Type::Error;
+        }
+        self.in_progress.push(key);
+
+        let declared = type_.borrow().as_ref().map(|type_| self.resolve_type(type_));
+
+        let value = value.borrow();
+        let type_ = match &*value {
+            // The type is known before checking the body, so that procedures can call themselves
+            Option::Some(expression @ AstExpression::Procedure(procedure)) => {
+                let (parameters, return_type) = self.check_procedure_signature(&procedure.borrow());
+                let signature = Type::Procedure(parameters, Box::new(return_type.clone()));
+                self.expression_types.insert(expression_key(expression).unwrap(), signature.clone());
+                if let Option::Some(declared) = &declared {
+                    self.expect_type(&signature, declared, procedure.borrow().span);
+                }
+
+                self.in_progress.pop();
+                self.declaration_types.insert(key, declared.unwrap_or(signature));
+                self.check_procedure_body(&procedure.borrow(), return_type);
+                return self.declaration_types[&key].clone();
+            }
+
+            // Struct and enum types may refer to themselves, e.g. through a pointer
+            Option::Some(expression @ AstExpression::Struct(struct_)) => {
+                let type_ = Type::Type(Box::new(Type::Struct(declaration.clone())));
+                self.expression_types.insert(expression_key(expression).unwrap(), type_.clone());
+                self.in_progress.pop();
+                self.declaration_types.insert(key, type_.clone());
+
+                // Checking a field reports it having type void like any other declaration
+                for field in &struct_.borrow().fields {
+                    let field_type = self.check_declaration(field);
+                    if self.contains_by_value(&field_type, declaration, &mut Vec::new()) {
+                        self.diagnostics.push(
+                            Diagnostic::error(format!("Struct '{}' cannot contain itself", name.identifier()), field.borrow().name.span)
+                                .with_help(format!("store a pointer instead ('^{}')", name.identifier()))
+                        );
+                    }
+                }
+                return type_;
+            }
+
+            Option::Some(expression @ AstExpression::Enum(enum_)) => {
+                let type_ = Type::Type(Box::new(Type::Enum(declaration.clone())));
+                self.expression_types.insert(expression_key(expression).unwrap(), type_.clone());
+                self.in_progress.pop();
+                self.declaration_types.insert(key, type_.clone());
+
+                for variant in &enum_.borrow().variants {
+                    for payload in &variant.payload {
+                        self.resolve_type(&payload.borrow());
+                    }
+                }
+                return type_;
+            }
+
+            Option::Some(value) => {
+                let value_type = self.check_expression(value, declared.as_ref());
+
+                if let Type::Type(_) = value_type {
+                    if !constant {
+                        self.diagnostics.push(
+                            Diagnostic::error(String::from("Types can only be given a name with a constant"), value.span())
+                                .with_help(format!("use '{} :: ...'", name.identifier()))
+                        );
+                    }
+                }
+
+                match declared {
+                    Option::Some(declared) => {
+                        self.expect_type(&value_type, &declared, value.span());
+                        declared
+                    }
+                    Option::None => value_type,
+                }
+            }
+
+            Option::None => declared.unwrap_or(Type::Error),
+        };
+
+        if type_ == Type::Void {
+            self.diagnostics.push(Diagnostic::error(format!("'{}' cannot have type void", name.identifier()), name.span));
+        }
+
+        self.in_progress.pop();
+        self.declaration_types.insert(key, type_.clone());
+        type_
+    }
+
+    /// Checks the parameters (and their default values) and the return type of a procedure
+    fn check_procedure_signature(&mut self, procedure: &AstProcedure) -> (Vec<Type>, Type) {
+        let parameters = procedure.arguments.iter()
+            .map(|argument| self.check_declaration(argument))
+            .collect();
+
+        let return_type = match &*procedure.return_type.borrow() {
+            Option::Some(return_type) => self.resolve_type(return_type),
+            Option::None => Type::Void,
+        };
+
+        (parameters, return_type)
+    }
+
+    fn check_procedure_body(&mut self, procedure: &AstProcedure, return_type: Type) {
+        self.return_types.push(return_type.clone());
+
+        let scope = procedure.scope.borrow();
+        let value_type = self.check_scope(&scope, Option::Some(&return_type));
+        if let Option::Some(value) = &scope.value {
+            let span = value.borrow().span();
+            self.expect_type(&value_type, &return_type, span);
+        }
+
+        self.return_types.pop();
+    }
+
+    /// Checks the statements of the scope and returns the type of its value, `void` if it has none
+    fn check_scope(&mut self, scope: &AstScope, expected: Option<&Type>) -> Type {
+        for statement in &scope.statements {
+            self.check_statement(&statement.borrow());
+        }
+
+        match &scope.value {
+            Option::Some(value) => self.check_expression(&value.borrow(), expected),
+            Option::None => Type::Void,
+        }
+    }
+
+    fn check_statement(&mut self, statement: &AstStatement) {
+        match statement {
+            AstStatement::Expression(expression) => {
+                self.check_expression(&expression.borrow(), Option::None);
+            }
+
+            AstStatement::Scope(scope) => {
+                self.check_scope(&scope.borrow(), Option::None);
+            }
+
+            AstStatement::Declaration(declaration) => {
+                self.check_declaration(declaration);
+            }
+
+            AstStatement::Assignment(assignment) => {
+                let assignment = assignment.borrow();
+                let (left, right) = (assignment.left.borrow(), assignment.right.borrow());
+
+                let left_type = self.check_expression(&left, Option::None);
+                let right_type = self.check_expression(&right, Option::Some(&left_type));
+
+                if !self.is_place(&left) {
+                    self.diagnostics.push(
+                        Diagnostic::error(String::from("Invalid left-hand side of assignment"), assignment.operator.span)
+                            .with_label(left.span(), String::from("cannot assign to this expression"))
+                    );
+                } else if let Option::Some(name) = self.assigned_name(&left) {
+                    let name = name.borrow();
+                    match name.declaration.as_ref().and_then(Weak::upgrade) {
+                        Option::Some(declaration) if declaration.borrow().constant => {
+                            self.diagnostics.push(
+                                Diagnostic::error(format!("Cannot assign to constant '{}'", name.token.identifier()), assignment.operator.span)
+                                    .with_label(declaration.borrow().name.span, String::from("declared as a constant here"))
+                            );
+                        }
+                        Option::Some(_) => {}
+                        Option::None => {
+                            self.diagnostics.push(Diagnostic::error(
+                                format!("Cannot assign to built-in '{}'", name.token.identifier()),
+                                assignment.operator.span,
+                            ));
+                        }
+                    }
+                }
+
+                let operation = match assignment.operator.kind {
+                    TokenKind::PlusEquals => TokenKind::Plus,
+                    TokenKind::MinusEquals => TokenKind::Minus,
+                    TokenKind::AsteriskEquals => TokenKind::Asterisk,
+                    TokenKind::SlashEquals => TokenKind::Slash,
+                    TokenKind::PercentEquals => TokenKind::Percent,
+                    TokenKind::AmpersandEquals => TokenKind::Ampersand,
+                    TokenKind::PipeEquals => TokenKind::Pipe,
+                    TokenKind::CaretEquals => TokenKind::Caret,
+                    TokenKind::LessThanLessThanEquals => TokenKind::LessThanLessThan,
+                    TokenKind::GreaterThanGreaterThanEquals => TokenKind::GreaterThanGreaterThan,
+                    _ => {
+                        self.expect_type(&right_type, &left_type, right.span());
+                        return;
+                    }
+                };
+
+                let result = self.binary_type(&operation, &assignment.operator, &left_type, &right_type);
+                self.expect_type(&result, &left_type, assignment.operator.span);
+            }
+
+            AstStatement::If(if_) => {
+                self.check_if(&if_.borrow(), Option::None, false);
+            }
+
+            AstStatement::Match(match_) => {
+                self.check_match(&match_.borrow(), Option::None, false);
+            }
+
+            AstStatement::While(while_) => {
+                let while_ = while_.borrow();
+                self.check_condition(&while_.condition.borrow());
+                self.check_scope(&while_.body.borrow(), Option::None);
+            }
+
+            AstStatement::Loop(loop_) => {
+                self.check_scope(&loop_.borrow().body.borrow(), Option::None);
+            }
+
+            AstStatement::For(for_) => {
+                let for_ = for_.borrow();
+                let (start, end) = (for_.start.borrow(), for_.end.borrow());
+
+                let (start_type, end_type) = self.check_operands(&start, &end, Option::None);
+                let variable_type = if start_type == Type::Error || end_type == Type::Error {
+                    Type::Error
+                } else if !start_type.is_integer() || start_type != end_type {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("Expected a range of integers got {}..{}", start_type, end_type),
+                        start.span().to(end.span()),
+                    ));
+                    Type::Error
+                } else {
+                    start_type
+                };
+
+                self.declaration_types.insert(Rc::as_ptr(&for_.variable), variable_type);
+                self.check_scope(&for_.body.borrow(), Option::None);
+            }
+
+            AstStatement::Return(return_) => {
+                if let Option::Some(value) = &return_.borrow().value {
+                    let return_type = self.return_types.last().cloned().unwrap_or(Type::Error);
+                    let value = value.borrow();
+                    let type_ = self.check_expression(&value, Option::Some(&return_type));
+                    self.expect_type(&type_, &return_type, value.span());
+                }
+            }
+
+            AstStatement::Break(_) |
+            AstStatement::Continue(_) |
+            AstStatement::Error => {}
+        }
+    }
+
+    /// Checks an `if`, whose branches all have to agree on a type when it is used as a value
+    fn check_if(&mut self, if_: &AstIf, expected: Option<&Type>, as_value: bool) -> Type {
+        self.check_condition(&if_.condition.borrow());
+
+        let expected = if as_value { expected } else { Option::None };
+        let then_type = self.check_scope(&if_.then_scope.borrow(), expected);
+
+        let expected_else = if as_value { Option::Some(expected.unwrap_or(&then_type)) } else { Option::None };
+        let (else_type, else_span) = match &if_.else_ {
+            Option::Some(AstElse::If(else_if)) => {
+                let else_if = else_if.borrow();
+                (self.check_if(&else_if, expected_else, as_value), Option::Some(else_if.span))
+            }
+            Option::Some(AstElse::Scope(scope)) => {
+                let scope = scope.borrow();
+                let span = scope.value.as_ref().map(|value| value.borrow().span());
+                (self.check_scope(&scope, expected_else), span)
+            }
+            Option::None => (Type::Void, Option::None),
+        };
+
+        if !as_value {
+            return Type::Void;
+        }
+        if let Option::Some(span) = else_span {
+            self.expect_type(&else_type, &then_type, span);
+        }
+        then_type
+    }
+
+    /// Checks a `match` against the type of the matched value, whose arms all have to agree on a type
+    /// when it is used as a value
+    fn check_match(&mut self, match_: &AstMatch, expected: Option<&Type>, as_value: bool) -> Type {
+        let value = match_.value.borrow();
+        let value_type = self.check_expression(&value, Option::None);
+
+        let enum_ = match &value_type {
+            Type::Enum(declaration) => declared_enum(declaration),
+            _ => Option::None,
+        };
+
+        if let Option::Some(enum_) = &enum_ {
+            let diagnostics = match_.check_variants(&enum_.borrow());
+            self.diagnostics.extend(diagnostics);
+        } else if value_type != Type::Error {
+            for arm in &match_.arms {
+                if let AstPattern::Variant(pattern) = &arm.pattern {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("Variant patterns can only match an enum, not {}", value_type),
+                        pattern.borrow().span,
+                    ));
+                }
+            }
+            if !match_.arms.iter().any(|arm| matches!(arm.pattern, AstPattern::Wildcard(_))) {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("Match on a value of type {} does not cover every value", value_type), match_.span)
+                        .with_help(String::from("add a '_' arm"))
+                );
+            }
+        }
+
+        let mut result: Option<Type> = Option::None;
+        for arm in &match_.arms {
+            match &arm.pattern {
+                AstPattern::Variant(pattern) => {
+                    let pattern = pattern.borrow();
+                    let payload = enum_.as_ref()
+                        .and_then(|enum_| {
+                            enum_.borrow().variants.iter()
+                                .find(|variant| variant.name.kind == pattern.name.kind)
+                                .map(|variant| variant.payload.clone())
+                        })
+                        .unwrap_or_default();
+
+                    for (index, binding) in pattern.bindings.iter().enumerate() {
+                        if let Option::Some(binding) = binding {
+                            let type_ = match payload.get(index) {
+                                Option::Some(type_) => self.resolve_type(&type_.borrow()),
+                                Option::None => Type::Error,
+                            };
+                            self.declaration_types.insert(Rc::as_ptr(binding), type_);
+                        }
+                    }
+                }
+
+                AstPattern::Literal(literal) => {
+                    let literal = AstExpression::Literal(literal.clone());
+                    let type_ = self.check_expression(&literal, Option::Some(&value_type));
+                    self.expect_type(&type_, &value_type, literal.span());
+                }
+
+                AstPattern::Wildcard(_) => {}
+            }
+
+            let expected_arm = if as_value { result.as_ref().or(expected) } else { Option::None };
+            let body = arm.body.borrow();
+            let arm_type = self.check_scope(&body, expected_arm);
+
+            if as_value {
+                match &result {
+                    Option::Some(result) => {
+                        if let Option::Some(value) = &body.value {
+                            let span = value.borrow().span();
+                            self.expect_type(&arm_type, result, span);
+                        }
+                    }
+                    Option::None => result = Option::Some(arm_type),
+                }
+            }
+        }
+
+        if as_value {
+            result.unwrap_or(Type::Void)
+        } else {
+            Type::Void
+        }
+    }
+
+    /// Checks both operands of a binary operation, where each operand's type is used to give
+    /// a number literal on the other side the same type, e.g. `1 + x` where `x: u8` or `1 + 0.5`
+    fn check_operands(&mut self, left: &AstExpression, right: &AstExpression, expected: Option<&Type>) -> (Type, Type) {
+        let right_first = matches!(
+            (untyped_literal(left), untyped_literal(right)),
+            (Option::Some(_), Option::None) | (Option::Some(false), Option::Some(true))
+        );
+
+        if right_first {
+            let right_type = self.check_expression(right, expected);
+            let left_type = self.check_expression(left, Option::Some(&right_type));
+            (left_type, right_type)
+        } else {
+            let left_type = self.check_expression(left, expected);
+            let right_type = self.check_expression(right, Option::Some(&left_type));
+            (left_type, right_type)
+        }
+    }
+
+    /// The type of `left operation right`, where `operator` is the token it was written with
+    fn binary_type(&mut self, operation: &TokenKind, operator: &Token, left: &Type, right: &Type) -> Type {
+        let comparison = matches!(
+            operation,
+            TokenKind::EqualsEquals |
+            TokenKind::ExclamationMarkEquals |
+            TokenKind::LessThan |
+            TokenKind::LessThanEquals |
+            TokenKind::GreaterThan |
+            TokenKind::GreaterThanEquals |
+            TokenKind::AmpersandAmpersand |
+            TokenKind::PipePipe
+        );
+
+        if *left == Type::Error || *right == Type::Error {
+            return if comparison { Type::Bool } else { Type::Error };
+        }
+
+        let valid = match operation {
+            TokenKind::Plus |
+            TokenKind::Minus |
+            TokenKind::Asterisk |
+            TokenKind::Slash |
+            TokenKind::Percent => left.is_number() && left == right,
+
+            TokenKind::Ampersand |
+            TokenKind::Pipe |
+            TokenKind::Caret => (left.is_integer() || *left == Type::Bool) && left == right,
+
+            TokenKind::LessThanLessThan |
+            TokenKind::GreaterThanGreaterThan => left.is_integer() && right.is_integer(),
+
+            TokenKind::LessThan |
+            TokenKind::LessThanEquals |
+            TokenKind::GreaterThan |
+            TokenKind::GreaterThanEquals => (left.is_number() || *left == Type::Char) && left == right,
+
+            TokenKind::EqualsEquals |
+            TokenKind::ExclamationMarkEquals => left == right && !matches!(left, Type::Void | Type::Type(_)),
+
+            TokenKind::AmpersandAmpersand |
+            TokenKind::PipePipe => *left == Type::Bool && *right == Type::Bool,
+
+            _ => false,
+        };
+
+        if !valid {
+            self.diagnostics.push(Diagnostic::error(
+                format!("Cannot apply {} to {} and {}", operator.kind, left, right),
+                operator.span,
+            ));
+            return Type::Error;
+        }
+
+        if comparison {
+            Type::Bool
+        } else {
+            left.clone()
+        }
+    }
+
+    fn check_expression(&mut self, expression: &AstExpression, expected: Option<&Type>) -> Type {
+        self.check_maybe_negated_expression(expression, expected, false)
+    }
+
+    /// Checks an expression that is the operand of a unary '-' when `negated` is set,
+    /// which lets an integer literal be the minimum of a signed type
+    fn check_maybe_negated_expression(&mut self, expression: &AstExpression, expected: Option<&Type>, negated: bool) -> Type {
+        let type_ = self.check_expression_kind(expression, expected, negated);
+        if let Option::Some(key) = expression_key(expression) {
+            self.expression_types.insert(key, type_.clone());
+        }
+        type_
+    }
+
+    fn check_expression_kind(&mut self, expression: &AstExpression, expected: Option<&Type>, negated: bool) -> Type {
+        match expression {
+            AstExpression::Procedure(procedure) => {
+                let procedure = procedure.borrow();
+                let (parameters, return_type) = self.check_procedure_signature(&procedure);
+                self.check_procedure_body(&procedure, return_type.clone());
+                Type::Procedure(parameters, Box::new(return_type))
+            }
+
+            AstExpression::Call(call) => self.check_call(&call.borrow()),
+
+            AstExpression::Struct(struct_) => {
+                self.diagnostics.push(Diagnostic::error(
+                    String::from("A struct type can only be the value of a constant declaration"),
+                    struct_.borrow().span,
+                ));
+                Type::Error
+            }
+
+            AstExpression::Enum(enum_) => {
+                self.diagnostics.push(Diagnostic::error(
+                    String::from("An enum type can only be the value of a constant declaration"),
+                    enum_.borrow().span,
+                ));
+                Type::Error
+            }
+
+            AstExpression::StructLiteral(literal) => {
+                let literal = literal.borrow();
+                let type_ = self.resolve_type(&literal.type_.borrow());
+
+                let struct_ = match &type_ {
+                    Type::Struct(declaration) => declared_struct(declaration),
+                    Type::Error => Option::None,
+                    _ => {
+                        self.diagnostics.push(Diagnostic::error(format!("{} is not a struct", type_), literal.type_.borrow().span()));
+                        Option::None
+                    }
+                };
+
+                let bound = struct_.as_ref().map(|struct_| literal.bind_fields(&struct_.borrow()));
+                match (struct_, bound) {
+                    (Option::Some(struct_), Option::Some(Result::Ok(bound))) => {
+                        for (field, value) in struct_.borrow().fields.iter().zip(&bound) {
+                            if let Option::Some(value) = value {
+                                let field_type = self.check_declaration(field);
+                                let value = value.borrow();
+                                let value_type = self.check_expression(&value, Option::Some(&field_type));
+                                self.expect_type(&value_type, &field_type, value.span());
+                            }
+                        }
+                        type_
+                    }
+                    (_, bound) => {
+                        if let Option::Some(Result::Err(diagnostics)) = bound {
+                            self.diagnostics.extend(diagnostics);
+                        }
+                        for field in &literal.fields {
+                            self.check_expression(&field.value.borrow(), Option::None);
+                        }
+                        Type::Error
+                    }
+                }
+            }
+
+            AstExpression::Field(field) => {
+                let field = field.borrow();
+                let operand_type = self.check_expression(&field.operand.borrow(), Option::None);
+
+                match &operand_type {
+                    Type::Struct(declaration) => {
+                        let struct_ = declared_struct(declaration).expect("struct types are declared by a struct");
+                        let found = struct_.borrow().fields.iter().find(|member| member.borrow().name.kind == field.name.kind).cloned();
+                        match found {
+                            Option::Some(member) => self.check_declaration(&member),
+                            Option::None => {
+                                self.diagnostics.push(Diagnostic::error(
+                                    format!("Struct {} has no field named '{}'", operand_type, field.name.identifier()),
+                                    field.name.span,
+                                ));
+                                Type::Error
+                            }
+                        }
+                    }
+
+                    // 'Enum.Variant' is a value of the enum, or a procedure building one when the variant has a payload
+                    Type::Type(type_) if matches!(**type_, Type::Enum(_)) => {
+                        let declaration = match &**type_ {
+                            Type::Enum(declaration) => declaration.clone(),
+                            _ => unreachable!(),
+                        };
+                        let enum_ = declared_enum(&declaration).expect("enum types are declared by an enum");
+                        let payload = enum_.borrow().variants.iter()
+                            .find(|variant| variant.name.kind == field.name.kind)
+                            .map(|variant| variant.payload.clone());
+
+                        match payload {
+                            Option::Some(payload) if payload.is_empty() => (**type_).clone(),
+                            Option::Some(payload) => {
+                                let payload = payload.iter().map(|type_| self.resolve_type(&type_.borrow())).collect();
+                                Type::Procedure(payload, type_.clone())
+                            }
+                            Option::None => {
+                                self.diagnostics.push(Diagnostic::error(
+                                    format!("Enum {} has no variant named '{}'", type_, field.name.identifier()),
+                                    field.name.span,
+                                ));
+                                Type::Error
+                            }
+                        }
+                    }
+
+                    Type::Error => Type::Error,
+
+                    _ => {
+                        self.diagnostics.push(Diagnostic::error(
+                            format!("Cannot access field '{}' of a value of type {}", field.name.identifier(), operand_type),
+                            field.name.span,
+                        ));
+                        Type::Error
+                    }
+                }
+            }
+
+            AstExpression::ArrayLiteral(array) => {
+                let array = array.borrow();
+                let mut element_type = match expected {
+                    Option::Some(Type::Array(_, element)) |
+                    Option::Some(Type::Slice(element)) => Option::Some((**element).clone()),
+                    _ => Option::None,
+                };
+
+                for (index, element) in array.elements.iter().enumerate() {
+                    let element = element.borrow();
+                    let type_ = self.check_expression(&element, element_type.as_ref());
+                    match &element_type {
+                        Option::Some(element_type) if index > 0 || expected.is_some() => {
+                            self.expect_type(&type_, element_type, element.span());
+                        }
+                        _ => element_type = Option::Some(type_),
+                    }
+                }
+
+                match element_type {
+                    Option::Some(element_type) => Type::Array(array.elements.len() as u64, Box::new(element_type)),
+                    Option::None => {
+                        self.diagnostics.push(Diagnostic::error(String::from("Cannot infer the element type of an empty array"), array.span));
+                        Type::Error
+                    }
+                }
+            }
+
+            AstExpression::Index(index) => {
+                let index = index.borrow();
+                let operand_type = self.check_expression(&index.operand.borrow(), Option::None);
+                self.check_index(&index.index.borrow());
+
+                match operand_type {
+                    Type::Array(_, element) | Type::Slice(element) => *element,
+                    Type::Error => Type::Error,
+                    _ => {
+                        self.diagnostics.push(Diagnostic::error(format!("Cannot index a value of type {}", operand_type), index.span));
+                        Type::Error
+                    }
+                }
+            }
+
+            AstExpression::Slice(slice) => {
+                let slice = slice.borrow();
+                let operand_type = self.check_expression(&slice.operand.borrow(), Option::None);
+                for bound in slice.start.iter().chain(&slice.end) {
+                    self.check_index(&bound.borrow());
+                }
+
+                match operand_type {
+                    Type::Array(_, element) | Type::Slice(element) => Type::Slice(element),
+                    Type::Error => Type::Error,
+                    _ => {
+                        self.diagnostics.push(Diagnostic::error(format!("Cannot slice a value of type {}", operand_type), slice.span));
+                        Type::Error
+                    }
+                }
+            }
+
+            AstExpression::If(if_) => self.check_if(&if_.borrow(), expected, true),
+
+            AstExpression::Match(match_) => self.check_match(&match_.borrow(), expected, true),
+
+            AstExpression::Name(name) => {
+                let name = name.borrow();
+                match name.declaration.as_ref().and_then(Weak::upgrade) {
+                    Option::Some(declaration) => self.check_declaration(&declaration),
+                    Option::None => match name.token.identifier() {
+                        "true" | "false" => Type::Bool,
+                        // Anything else was already reported by the resolver
+                        identifier => builtin_type(identifier).map_or(Type::Error, |type_| Type::Type(Box::new(type_))),
+                    },
+                }
+            }
+
+            AstExpression::Literal(literal) => {
+                let token = literal.borrow().token.clone();
+                match token.kind {
+                    TokenKind::Integer(_, Option::Some(suffix)) |
+                    TokenKind::Float(_, Option::Some(suffix)) => Type::Number(suffix),
+
+                    // Without a suffix the literal becomes whatever number type is expected, 'int' otherwise
+                    TokenKind::Integer(value, Option::None) => {
+                        let number = match expected {
+                            Option::Some(Type::Number(number)) => *number,
+                            _ => NumberSuffix::I64,
+                        };
+                        if value > number.max_literal(negated) {
+                            self.diagnostics.push(Diagnostic::error(
+                                format!("Literal {} does not fit in {}", value, Type::Number(number)),
+                                token.span,
+                            ));
+                        }
+                        Type::Number(number)
+                    }
+
+                    TokenKind::Float(_, Option::None) => match expected {
+                        Option::Some(Type::Number(number)) if number.is_float() => Type::Number(*number),
+                        _ => Type::Number(NumberSuffix::F64),
+                    },
+
+                    TokenKind::String(_) => Type::String,
+                    TokenKind::Char(_) => Type::Char,
+                    _ => Type::Error,
+                }
+            }
+
+            AstExpression::Unary(unary) => {
+                let unary = unary.borrow();
+                let operand = unary.operand.borrow();
+
+                match unary.operator.kind {
+                    TokenKind::Plus | TokenKind::Minus => {
+                        // Every '-' flips the sign, so '- -128' is 128 again
+                        let negated = negated != (unary.operator.kind == TokenKind::Minus);
+                        let type_ = self.check_maybe_negated_expression(&operand, expected, negated);
+                        match &type_ {
+                            Type::Number(number) if unary.operator.kind == TokenKind::Minus && !number.is_signed() => {
+                                self.diagnostics.push(Diagnostic::error(format!("Cannot negate a value of unsigned type {}", type_), unary.operator.span));
+                                Type::Error
+                            }
+                            Type::Number(_) | Type::Error => type_,
+                            _ => self.unary_error(&unary.operator, &type_),
+                        }
+                    }
+
+                    TokenKind::ExclamationMark => {
+                        let type_ = self.check_expression(&operand, Option::Some(&Type::Bool));
+                        match type_ {
+                            Type::Bool | Type::Error => type_,
+                            _ => self.unary_error(&unary.operator, &type_),
+                        }
+                    }
+
+                    TokenKind::Tilde => {
+                        let type_ = self.check_expression(&operand, expected);
+                        if type_.is_integer() || type_ == Type::Error {
+                            type_
+                        } else {
+                            self.unary_error(&unary.operator, &type_)
+                        }
+                    }
+
+                    TokenKind::Ampersand => {
+                        let pointee = match expected {
+                            Option::Some(Type::Pointer(pointee)) => Option::Some(&**pointee),
+                            _ => Option::None,
+                        };
+                        let type_ = self.check_expression(&operand, pointee);
+                        if !self.is_place(&operand) {
+                            self.diagnostics.push(
+                                Diagnostic::error(String::from("Cannot take the address of a temporary value"), unary.operator.span)
+                                    .with_label(operand.span(), String::from("this value is not stored anywhere"))
+                                    .with_help(String::from("store it in a variable first"))
+                            );
+                        }
+                        match type_ {
+                            Type::Error => Type::Error,
+                            type_ => Type::Pointer(Box::new(type_)),
+                        }
+                    }
+
+                    TokenKind::Caret => {
+                        let pointer = expected.map(|expected| Type::Pointer(Box::new(expected.clone())));
+                        match self.check_expression(&operand, pointer.as_ref()) {
+                            Type::Pointer(pointee) => *pointee,
+                            Type::Error => Type::Error,
+                            type_ => {
+                                self.diagnostics.push(Diagnostic::error(format!("Cannot dereference a value of type {}", type_), unary.operator.span));
+                                Type::Error
+                            }
+                        }
+                    }
+
+                    _ => Type::Error,
+                }
+            }
+
+            AstExpression::Binary(binary) => {
+                let binary = binary.borrow();
+                let (left, right) = (binary.left.borrow(), binary.right.borrow());
+
+                let (left_type, right_type) = match binary.operator.kind {
+                    TokenKind::AmpersandAmpersand | TokenKind::PipePipe => (
+                        self.check_expression(&left, Option::Some(&Type::Bool)),
+                        self.check_expression(&right, Option::Some(&Type::Bool)),
+                    ),
+                    TokenKind::LessThanLessThan | TokenKind::GreaterThanGreaterThan => (
+                        self.check_expression(&left, expected),
+                        self.check_expression(&right, Option::None),
+                    ),
+                    TokenKind::EqualsEquals |
+                    TokenKind::ExclamationMarkEquals |
+                    TokenKind::LessThan |
+                    TokenKind::LessThanEquals |
+                    TokenKind::GreaterThan |
+                    TokenKind::GreaterThanEquals => self.check_operands(&left, &right, Option::None),
+                    _ => self.check_operands(&left, &right, expected),
+                };
+
+                self.binary_type(&binary.operator.kind, &binary.operator, &left_type, &right_type)
+            }
+
+            AstExpression::Error(_) => Type::Error,
+        }
+    }
+
+    fn unary_error(&mut self, operator: &Token, type_: &Type) -> Type {
+        self.diagnostics.push(Diagnostic::error(format!("Cannot apply {} to {}", operator.kind, type_), operator.span));
+        Type::Error
+    }
+
+    fn check_index(&mut self, index: &AstExpression) {
+        let type_ = self.check_expression(index, Option::Some(&Type::Number(NumberSuffix::I64)));
+        if !type_.is_integer() && type_ != Type::Error {
+            self.diagnostics.push(Diagnostic::error(format!("Expected an integer index got {}", type_), index.span()));
+        }
+    }
+
+    fn check_call(&mut self, call: &AstCall) -> Type {
+        let callee = call.callee.borrow();
+        let callee_type = self.check_expression(&callee, Option::None);
+
+        let (parameters, return_type) = match callee_type {
+            Type::Procedure(parameters, return_type) => (parameters, *return_type),
+            type_ => {
+                if type_ != Type::Error {
+                    self.diagnostics.push(Diagnostic::error(format!("Cannot call a value of type {}", type_), callee.span()));
+                }
+                for argument in &call.arguments {
+                    self.check_expression(&argument.value.borrow(), Option::None);
+                }
+                return Type::Error;
+            }
+        };
+
+        // Named arguments and default values need the declaration of the procedure
+        let procedure = match &*callee {
+            AstExpression::Name(name) => name.borrow().declaration.as_ref().and_then(Weak::upgrade).and_then(|declaration| declared_procedure(&declaration)),
+            _ => Option::None,
+        };
+
+        let bound = match procedure {
+            Option::Some(procedure) => match call.bind_arguments(&procedure.borrow()) {
+                Result::Ok(bound) => Option::Some(bound),
+                Result::Err(diagnostics) => {
+                    self.diagnostics.extend(diagnostics);
+                    Option::None
+                }
+            },
+            Option::None => {
+                if let Option::Some(named) = call.arguments.iter().find(|argument| argument.name.is_some()) {
+                    self.diagnostics.push(Diagnostic::error(
+                        String::from("Named arguments can only be used when calling a procedure by the name it was declared with"),
+                        named.span(),
+                    ));
+                    Option::None
+                } else if call.arguments.len() != parameters.len() {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("Expected {} arguments got {}", parameters.len(), call.arguments.len()),
+                        call.span,
+                    ));
+                    Option::None
+                } else {
+                    Option::Some(call.arguments.iter().map(|argument| Option::Some(argument.value.clone())).collect())
+                }
+            }
+        };
+
+        match bound {
+            Option::Some(bound) => {
+                for (parameter, argument) in parameters.iter().zip(&bound) {
+                    if let Option::Some(argument) = argument {
+                        let argument = argument.borrow();
+                        let type_ = self.check_expression(&argument, Option::Some(parameter));
+                        self.expect_type(&type_, parameter, argument.span());
+                    }
+                }
+            }
+            Option::None => {
+                for argument in &call.arguments {
+                    self.check_expression(&argument.value.borrow(), Option::None);
+                }
+            }
+        }
+
+        return_type
+    }
+
+    fn resolve_type(&mut self, type_: &AstType) -> Type {
+        let key = type_ as *const AstType;
+        if let Option::Some(resolved) = self.resolved_types.get(&key) {
+            return resolved.clone();
+        }
+
+        let resolved = match type_ {
+            AstType::Name(name) => {
+                let name = name.borrow();
+                match name.declaration.as_ref().and_then(Weak::upgrade) {
+                    Option::Some(declaration) => match self.check_declaration(&declaration) {
+                        Type::Type(type_) => *type_,
+                        Type::Error => Type::Error,
+                        _ => {
+                            self.diagnostics.push(
+                                Diagnostic::error(format!("'{}' is not a type", name.token.identifier()), name.token.span)
+                                    .with_label(declaration.borrow().name.span, String::from("declared here"))
+                            );
+                            Type::Error
+                        }
+                    },
+                    Option::None => match builtin_type(name.token.identifier()) {
+                        Option::Some(type_) => type_,
+                        Option::None => {
+                            // Undeclared names were already reported by the resolver
+                            if name.token.identifier() == "true" || name.token.identifier() == "false" {
+                                self.diagnostics.push(Diagnostic::error(format!("'{}' is not a type", name.token.identifier()), name.token.span));
+                            }
+                            Type::Error
+                        }
+                    },
+                }
+            }
+
+            AstType::Pointer(pointer) => Type::Pointer(Box::new(self.resolve_type(&pointer.borrow().pointee.borrow()))),
+
+            AstType::Array(array) => {
+                let array = array.borrow();
+                let length = array.length.borrow();
+                let length_type = self.check_expression(&length, Option::Some(&Type::Number(NumberSuffix::I64)));
+                let element = self.resolve_type(&array.element.borrow());
+
+                match Checker::constant_integer(&length, 0) {
+                    Option::Some(value) if length_type.is_integer() => Type::Array(value, Box::new(element)),
+                    _ => {
+                        if length_type != Type::Error {
+                            self.diagnostics.push(Diagnostic::error(String::from("Array length must be a constant integer"), length.span()));
+                        }
+                        Type::Error
+                    }
+                }
+            }
+
+            AstType::Slice(slice) => Type::Slice(Box::new(self.resolve_type(&slice.borrow().element.borrow()))),
+
+            AstType::Procedure(procedure) => {
+                let procedure = procedure.borrow();
+                let parameters = procedure.parameters.iter()
+                    .map(|parameter| self.resolve_type(&parameter.borrow()))
+                    .collect();
+                let return_type = match &*procedure.return_type.borrow() {
+                    Option::Some(return_type) => self.resolve_type(return_type),
+                    Option::None => Type::Void,
+                };
+                Type::Procedure(parameters, Box::new(return_type))
+            }
+
+            AstType::Optional(optional) => Type::Optional(Box::new(self.resolve_type(&optional.borrow().inner.borrow()))),
+        };
+
+        self.resolved_types.insert(key, resolved.clone());
+        resolved
+    }
+
+    /// The value of an integer expression made of literals and constants, e.g. the length of an array type
+    fn constant_integer(expression: &AstExpression, depth: usize) -> Option<u64> {
+        // Constants that depend on themselves are reported elsewhere
+        if depth > 64 {
+            return Option::None;
+        }
+
+        match expression {
+            AstExpression::Literal(literal) => match literal.borrow().token.kind {
+                TokenKind::Integer(value, _) => Option::Some(value),
+                _ => Option::None,
+            },
+
+            AstExpression::Name(name) => {
+                let declaration = name.borrow().declaration.as_ref().and_then(Weak::upgrade)?;
+                let declaration = declaration.borrow();
+                if !declaration.constant {
+                    return Option::None;
+                }
+                let value = declaration.value.borrow();
+                Checker::constant_integer(value.as_ref()?, depth + 1)
+            }
+
+            AstExpression::Binary(binary) => {
+                let binary = binary.borrow();
+                let left = Checker::constant_integer(&binary.left.borrow(), depth + 1)?;
+                let right = Checker::constant_integer(&binary.right.borrow(), depth + 1)?;
+                match binary.operator.kind {
+                    TokenKind::Plus => left.checked_add(right),
+                    TokenKind::Minus => left.checked_sub(right),
+                    TokenKind::Asterisk => left.checked_mul(right),
+                    TokenKind::Slash => left.checked_div(right),
+                    TokenKind::Percent => left.checked_rem(right),
+                    TokenKind::LessThanLessThan => left.checked_shl(u32::try_from(right).ok()?),
+                    TokenKind::GreaterThanGreaterThan => left.checked_shr(u32::try_from(right).ok()?),
+                    _ => Option::None,
+                }
+            }
+
+            _ => Option::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::*;
+    use crate::resolver::*;
+
+    /// The messages of the errors reported while checking `source`, which has to parse and resolve
+    fn errors(source: &str) -> Vec<String> {
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file(String::from("test.lang"), String::from(source));
+        let ast = Parser::new(&source_map, file_id).parse().expect("source should parse");
+        Resolver::new().resolve(&ast).expect("source should resolve");
+
+        match Checker::new().check(&ast) {
+            Result::Ok(()) => Vec::new(),
+            Result::Err(diagnostics) => diagnostics.into_iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                .map(|diagnostic| diagnostic.message)
+                .collect(),
+        }
+    }
+
+    fn assert_errors(source: &str, expected: &[&str]) {
+        assert_eq!(errors(source), expected, "while checking:\n{}", source);
+    }
+
+    #[test]
+    fn valid_program() {
+        assert_errors("
+            Vec2 :: struct { x: float, y: float = 0.0 }
+            Shape :: enum { Circle(float), Empty }
+            add :: (a: int, b: int = 1) -> int { a + b }
+            main :: () {
+                v := Vec2{ x = 1.0 };
+                v.y = v.x * 2;
+                p := &v;
+                p^.x += 1.0;
+                s := Shape.Circle(2.0);
+                r := match s { Circle(r) => r, Empty => 0.0 };
+                arr := [1, 2, 3];
+                slice := arr[1..];
+                slice[0] = add(1) + add(b = 2, a = 3);
+                for i in 0..3 { arr[i] = i; }
+                x: i8 = -128;
+                o: ?int = 5;
+            }
+        ", &[]);
+    }
+
+    #[test]
+    fn mismatched_declaration() {
+        assert_errors("a: int = 1.5;", &["Expected int got float"]);
+    }
+
+    #[test]
+    fn integer_literal_range() {
+        assert_errors("a: i8 = 127; b: i8 = -128; c: u8 = 255;", &[]);
+        assert_errors("a: i8 = 128;", &["Literal 128 does not fit in i8"]);
+        assert_errors("a: i8 = -129;", &["Literal 129 does not fit in i8"]);
+        assert_errors("a := 9223372036854775808;", &["Literal 9223372036854775808 does not fit in int"]);
+        assert_errors("a: i8 = - -128;", &["Literal 128 does not fit in i8"]);
+        assert_errors("a: i8 = - - -128; b: i8 = -+128;", &[]);
+    }
+
+    #[test]
+    fn negate_unsigned() {
+        assert_errors("a: u8 = 1; b := -a;", &["Cannot negate a value of unsigned type u8"]);
+    }
+
+    #[test]
+    fn binary_operands() {
+        assert_errors("a := 1 + \"a\";", &["Cannot apply '+' to int and string"]);
+        assert_errors("a := 1 + 1.5;", &[]);
+    }
+
+    #[test]
+    fn unary_operand() {
+        assert_errors("a := !1;", &["Cannot apply '!' to int"]);
+    }
+
+    #[test]
+    fn condition_must_be_bool() {
+        assert_errors("main :: () { if 1 {} }", &["Expected bool got int"]);
+    }
+
+    #[test]
+    fn return_type() {
+        assert_errors("f :: () -> int { return 1.5; }", &["Expected int got float"]);
+    }
+
+    #[test]
+    fn cyclic_declarations() {
+        assert_errors("A :: B; B :: A;", &["The type of 'A' depends on itself"]);
+    }
+
+    #[test]
+    fn void_declarations() {
+        assert_errors("f :: () {} main :: () { x := f(); }", &["'x' cannot have type void"]);
+        assert_errors("S :: struct { x: void }", &["'x' cannot have type void"]);
+    }
+
+    #[test]
+    fn struct_containing_itself() {
+        assert_errors("S :: struct { s: S }", &["Struct 'S' cannot contain itself"]);
+        assert_errors("S :: struct { s: [2]S }", &["Struct 'S' cannot contain itself"]);
+        assert_errors("S :: struct { t: T } T :: struct { s: ?S }", &["Struct 'S' cannot contain itself"]);
+        assert_errors("S :: struct { s: ^S, all: []S }", &[]);
+    }
+
+    #[test]
+    fn type_in_variable() {
+        assert_errors("T := int;", &["Types can only be given a name with a constant"]);
+    }
+
+    #[test]
+    fn not_a_type() {
+        assert_errors("x := 1; y: x = 1;", &["'x' is not a type"]);
+    }
+
+    #[test]
+    fn array_length_must_be_constant() {
+        assert_errors("N :: 2; a: [N]int = [1, 2];", &[]);
+        assert_errors("n := 2; a: [n]int = [1, 2];", &["Array length must be a constant integer"]);
+    }
+
+    #[test]
+    fn assign_to_temporary() {
+        assert_errors("f :: () -> [2]int { [1, 2] } main :: () { f()[0] = 1; }", &["Invalid left-hand side of assignment"]);
+        assert_errors("f :: (a: ^[2]int) -> []int { a^[0..] } main :: () { a := [1, 2]; f(&a)[0] = 1; }", &[]);
+    }
+
+    #[test]
+    fn assign_to_constant() {
+        assert_errors("C :: 5; main :: () { C = 6; }", &["Cannot assign to constant 'C'"]);
+        assert_errors("main :: () { main = main; }", &["Cannot assign to constant 'main'"]);
+        assert_errors("main :: () { true = false; }", &["Cannot assign to built-in 'true'"]);
+        assert_errors("A :: [1, 2]; main :: () { A[0] = 3; }", &["Cannot assign to constant 'A'"]);
+    }
+
+    #[test]
+    fn address_of_temporary() {
+        assert_errors("f :: () -> [2]int { [1, 2] } main :: () { p := &f()[0]; }", &["Cannot take the address of a temporary value"]);
+        assert_errors("f :: (a: ^[2]int) -> []int { a^[0..] } main :: () { a := [1, 2]; p := &f(&a)[0]; }", &[]);
+    }
+
+    #[test]
+    fn dereference_non_pointer() {
+        assert_errors("a := 1; b := a^;", &["Cannot dereference a value of type int"]);
+    }
+
+    #[test]
+    fn for_range() {
+        assert_errors("main :: () { for i in 0..1.5 {} }", &["Expected a range of integers got float..float"]);
+    }
+
+    #[test]
+    fn struct_literal_of_non_struct() {
+        assert_errors("a := int{};", &["int is not a struct"]);
+    }
+
+    #[test]
+    fn unknown_field() {
+        assert_errors("S :: struct { x: int } s := S{ x = 1 }; y := s.y;", &["Struct S has no field named 'y'"]);
+        assert_errors("a := 1; b := a.x;", &["Cannot access field 'x' of a value of type int"]);
+    }
+
+    #[test]
+    fn unknown_variant() {
+        assert_errors("E :: enum { A } e := E.B;", &["Enum E has no variant named 'B'"]);
+    }
+
+    #[test]
+    fn match_coverage() {
+        assert_errors("E :: enum { A, B } main :: () { e := E.A; match e { A => {} } }", &["Match does not cover every variant"]);
+        assert_errors("main :: () { a := 1; match a { 0 => {} } }", &["Match on a value of type int does not cover every value"]);
+        assert_errors("main :: () { a := 1; match a { A => {}, _ => {} } }", &["Variant patterns can only match an enum, not int"]);
+    }
+
+    #[test]
+    fn empty_array() {
+        assert_errors("a := [];", &["Cannot infer the element type of an empty array"]);
+        assert_errors("a: [0]int = [];", &[]);
+    }
+
+    #[test]
+    fn index_and_slice() {
+        assert_errors("a := 1; b := a[0];", &["Cannot index a value of type int"]);
+        assert_errors("a := 1; b := a[0..1];", &["Cannot slice a value of type int"]);
+        assert_errors("a := [1]; b := a[1.5];", &["Expected an integer index got float"]);
+    }
+
+    #[test]
+    fn calls() {
+        assert_errors("a := 1; b := a();", &["Cannot call a value of type int"]);
+        assert_errors(
+            "add :: (a: int, b: int) -> int { a + b } f := add; c := f(a = 1, b = 2);",
+            &["Named arguments can only be used when calling a procedure by the name it was declared with"],
+        );
+        assert_errors("add :: (a: int, b: int) -> int { a + b } f := add; c := f(1);", &["Expected 2 arguments got 1"]);
+    }
+}
@@ -0,0 +1,154 @@
+pub use crate::token::*;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NoteKind {
+    Note,
+    Help,
+}
+
+impl fmt::Display for NoteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoteKind::Note => write!(f, "note"),
+            NoteKind::Help => write!(f, "help"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Note {
+    pub kind: NoteKind,
+    pub message: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<Note>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: String, span: Span) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message,
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn error(message: String, span: Span) -> Diagnostic {
+        Diagnostic::new(Severity::Error, message, span)
+    }
+
+    pub fn warning(message: String, span: Span) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, message, span)
+    }
+
+    pub fn with_label(mut self, span: Span, message: String) -> Diagnostic {
+        self.labels.push(Label { span, message });
+        self
+    }
+
+    pub fn with_note(mut self, message: String) -> Diagnostic {
+        self.notes.push(Note { kind: NoteKind::Note, message });
+        self
+    }
+
+    pub fn with_help(mut self, message: String) -> Diagnostic {
+        self.notes.push(Note { kind: NoteKind::Help, message });
+        self
+    }
+
+    /// Renders the diagnostic rustc-style: the message, the file location, the offending
+    /// source lines with the spans underlined and any notes or help text.
+    pub fn render(&self, source_map: &SourceMap) -> String {
+        let max_line = self.labels
+            .iter()
+            .map(|label| source_map.line_column(label.span).0)
+            .fold(source_map.line_column(self.span).0, usize::max);
+        let gutter = " ".repeat(max_line.to_string().len());
+
+        let (line, column) = source_map.line_column(self.span);
+        let mut output = format!("{}: {}\n", self.severity, self.message);
+        output += &format!("{}--> {}:{}:{}\n", gutter, source_map.file(self.span.file_id).path, line, column);
+        output += &format!("{} |\n", gutter);
+
+        let mut snippets = vec![(self.span, '^', "")];
+        for label in &self.labels {
+            snippets.push((label.span, '-', &label.message));
+        }
+        // Keep the primary file first, then go through the spans in source order
+        snippets.sort_by_key(|(span, _, _)| (span.file_id != self.span.file_id, span.file_id.0, span.start));
+
+        let mut current_file = self.span.file_id;
+        let mut current_line = 0;
+        for (span, marker, message) in snippets {
+            let file = source_map.file(span.file_id);
+            let (line, column) = file.line_column(span.start);
+
+            if span.file_id != current_file {
+                current_file = span.file_id;
+                current_line = 0;
+                output += &format!("{}::: {}:{}:{}\n", gutter, file.path, line, column);
+                output += &format!("{} |\n", gutter);
+            }
+
+            let text = file.line(line);
+            let prefix: String = text
+                .chars()
+                .take(column - 1)
+                .map(|chr| if chr == '\t' { '\t' } else { ' ' })
+                .collect();
+            // Spans are in bytes, but the underline is drawn in characters
+            let underlined = source_map.text(span).split('\n').next().unwrap_or("");
+            let length = underlined.chars().count().max(1);
+
+            // Several spans on the same line share one copy of it
+            if line != current_line {
+                current_line = line;
+                output += &format!("{:>width$} | {}\n", line, text, width = gutter.len());
+            }
+            output += &format!("{} | {}{}", gutter, prefix, marker.to_string().repeat(length));
+            if !message.is_empty() {
+                output += &format!(" {}", message);
+            }
+            output += "\n";
+        }
+
+        if !self.notes.is_empty() {
+            output += &format!("{} |\n", gutter);
+        }
+        for note in &self.notes {
+            output += &format!("{} = {}: {}\n", gutter, note.kind, note.message);
+        }
+
+        output
+    }
+}
@@ -0,0 +1,881 @@
+pub use crate::checker::*;
+use std::collections::HashMap;
+
+/// Index of a declaration in `Hir::declarations`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct DeclarationId(pub usize);
+
+/// Identifies a loop for the `break` and `continue` statements that target it
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct LoopId(pub usize);
+
+/// The lowered form of a checked file: every name refers to its declaration by id
+/// and every expression carries its type, so later passes never have to look anything up in the AST
+#[derive(Clone, Debug)]
+pub struct Hir {
+    pub declarations: Vec<HirDeclaration>,
+    pub statements: Vec<HirStatement>,
+}
+
+impl Hir {
+    pub fn declaration(&self, id: DeclarationId) -> &HirDeclaration {
+        &self.declarations[id.0]
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum HirType {
+    Void,
+    Bool,
+    Char,
+    String,
+    Number(NumberSuffix),
+    Pointer(Box<HirType>),
+    Array(u64, Box<HirType>),
+    Slice(Box<HirType>),
+    Procedure(Vec<HirType>, Box<HirType>),
+    Optional(Box<HirType>),
+    Struct(DeclarationId),
+    Enum(DeclarationId),
+    /// The type of an expression naming a type
+    Type(Box<HirType>),
+}
+
+/// Any declared name: variables, constants, parameters, struct fields, loop variables and pattern bindings
+#[derive(Clone, Debug)]
+pub struct HirDeclaration {
+    pub name: String,
+    pub span: Span,
+    pub type_: HirType,
+    pub constant: bool,
+    /// The initial value, default value for parameters and fields, `None` when there is none
+    pub value: Option<HirExpression>,
+}
+
+#[derive(Clone, Debug)]
+pub struct HirBlock {
+    pub statements: Vec<HirStatement>,
+    pub value: Option<Box<HirExpression>>,
+}
+
+#[derive(Clone, Debug)]
+pub enum HirStatement {
+    Expression(HirExpression),
+    Block(HirBlock),
+    Declaration(DeclarationId),
+    Assignment {
+        target: HirExpression,
+        /// The operation of a compound assignment, e.g. `Add` for `+=`
+        operator: Option<HirBinaryOperator>,
+        value: HirExpression,
+    },
+    While {
+        id: LoopId,
+        condition: HirExpression,
+        body: HirBlock,
+    },
+    Loop {
+        id: LoopId,
+        body: HirBlock,
+    },
+    For {
+        id: LoopId,
+        variable: DeclarationId,
+        start: HirExpression,
+        end: HirExpression,
+        body: HirBlock,
+    },
+    Break(LoopId),
+    Continue(LoopId),
+    Return(Option<HirExpression>),
+}
+
+#[derive(Clone, Debug)]
+pub struct HirExpression {
+    pub kind: HirExpressionKind,
+    pub type_: HirType,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub enum HirExpressionKind {
+    Integer(u64),
+    Float(f64),
+    String(String),
+    Char(char),
+    Bool(bool),
+    Declaration(DeclarationId),
+    /// A built-in type used as a value, e.g. `int` in `Number :: int;`
+    Type,
+    Procedure(Box<HirProcedure>),
+    Struct {
+        fields: Vec<DeclarationId>,
+    },
+    Enum {
+        variants: Vec<HirVariant>,
+    },
+    Call {
+        callee: Box<HirExpression>,
+        /// One per parameter in declaration order, `None` where the parameter's default value is used
+        arguments: Vec<Option<HirExpression>>,
+    },
+    StructLiteral {
+        struct_: DeclarationId,
+        /// One per field in declaration order, `None` where the field's default value is used
+        fields: Vec<Option<HirExpression>>,
+    },
+    /// Builds a value of an enum
+    Variant {
+        enum_: DeclarationId,
+        variant: usize,
+        payload: Vec<HirExpression>,
+    },
+    /// A variant with a payload used as a procedure without calling it
+    VariantConstructor {
+        enum_: DeclarationId,
+        variant: usize,
+    },
+    Field {
+        operand: Box<HirExpression>,
+        field: usize,
+    },
+    Array(Vec<HirExpression>),
+    Index {
+        operand: Box<HirExpression>,
+        index: Box<HirExpression>,
+    },
+    Slice {
+        operand: Box<HirExpression>,
+        start: Option<Box<HirExpression>>,
+        end: Option<Box<HirExpression>>,
+    },
+    /// `else if` is an `else` block holding another `If`
+    If {
+        condition: Box<HirExpression>,
+        then_block: HirBlock,
+        else_block: Option<HirBlock>,
+    },
+    Match {
+        value: Box<HirExpression>,
+        arms: Vec<HirMatchArm>,
+    },
+    Unary {
+        operator: HirUnaryOperator,
+        operand: Box<HirExpression>,
+    },
+    Binary {
+        operator: HirBinaryOperator,
+        left: Box<HirExpression>,
+        right: Box<HirExpression>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct HirProcedure {
+    pub parameters: Vec<DeclarationId>,
+    pub return_type: HirType,
+    pub body: HirBlock,
+}
+
+#[derive(Clone, Debug)]
+pub struct HirVariant {
+    pub name: String,
+    pub payload: Vec<HirType>,
+}
+
+#[derive(Clone, Debug)]
+pub struct HirMatchArm {
+    pub pattern: HirPattern,
+    pub body: HirBlock,
+}
+
+#[derive(Clone, Debug)]
+pub enum HirPattern {
+    Wildcard,
+    Variant {
+        variant: usize,
+        /// `None` for values ignored with `_`
+        bindings: Vec<Option<DeclarationId>>,
+    },
+    Literal(HirExpression),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HirUnaryOperator {
+    Negate,
+    Not,
+    BitwiseNot,
+    AddressOf,
+    Dereference,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HirBinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    ShiftLeft,
+    ShiftRight,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+impl HirBinaryOperator {
+    /// The operator for a binary operator token, or the operation of a compound assignment token
+    pub fn from_token(kind: &TokenKind) -> Option<HirBinaryOperator> {
+        Option::Some(match kind {
+            TokenKind::Plus | TokenKind::PlusEquals => HirBinaryOperator::Add,
+            TokenKind::Minus | TokenKind::MinusEquals => HirBinaryOperator::Subtract,
+            TokenKind::Asterisk | TokenKind::AsteriskEquals => HirBinaryOperator::Multiply,
+            TokenKind::Slash | TokenKind::SlashEquals => HirBinaryOperator::Divide,
+            TokenKind::Percent | TokenKind::PercentEquals => HirBinaryOperator::Remainder,
+            TokenKind::Ampersand | TokenKind::AmpersandEquals => HirBinaryOperator::BitwiseAnd,
+            TokenKind::Pipe | TokenKind::PipeEquals => HirBinaryOperator::BitwiseOr,
+            TokenKind::Caret | TokenKind::CaretEquals => HirBinaryOperator::BitwiseXor,
+            TokenKind::LessThanLessThan | TokenKind::LessThanLessThanEquals => HirBinaryOperator::ShiftLeft,
+            TokenKind::GreaterThanGreaterThan | TokenKind::GreaterThanGreaterThanEquals => HirBinaryOperator::ShiftRight,
+            TokenKind::EqualsEquals => HirBinaryOperator::Equal,
+            TokenKind::ExclamationMarkEquals => HirBinaryOperator::NotEqual,
+            TokenKind::LessThan => HirBinaryOperator::Less,
+            TokenKind::LessThanEquals => HirBinaryOperator::LessEqual,
+            TokenKind::GreaterThan => HirBinaryOperator::Greater,
+            TokenKind::GreaterThanEquals => HirBinaryOperator::GreaterEqual,
+            TokenKind::AmpersandAmpersand => HirBinaryOperator::And,
+            TokenKind::PipePipe => HirBinaryOperator::Or,
+            _ => return Option::None,
+        })
+    }
+}
+
+/// Lowers a tree that passed name resolution and type checking into `Hir`,
+/// lowering anything with errors in it is a bug
+pub struct Lowerer<'a> {
+    checker: &'a Checker,
+    declaration_ids: HashMap<*const RefCell<AstDeclaration>, DeclarationId>,
+    /// Filled in as the declarations are reached, a declaration can be referenced before that
+    declarations: Vec<Option<HirDeclaration>>,
+    /// Keyed by the `AstWhile`, `AstLoop` or `AstFor` node
+    loop_ids: HashMap<*const (), LoopId>,
+}
+
+impl<'a> Lowerer<'a> {
+    pub fn new(checker: &'a Checker) -> Lowerer<'a> {
+        Lowerer {
+            checker,
+            declaration_ids: HashMap::new(),
+            declarations: Vec::new(),
+            loop_ids: HashMap::new(),
+        }
+    }
+
+    pub fn lower(mut self, ast: &Rc<RefCell<Ast>>) -> Hir {
+        let statements = match &*ast.borrow() {
+            Ast::File(file) => {
+                let file = file.borrow();
+                let scope = file.scope.borrow();
+                scope.statements.iter().map(|statement| self.lower_statement(&statement.borrow())).collect()
+            }
+            Ast::Statement(statement) => vec![self.lower_statement(&statement.borrow())],
+        };
+
+        Hir {
+            declarations: self.declarations.into_iter()
+                .map(|declaration| declaration.expect("every referenced declaration is part of the tree"))
+                .collect(),
+            statements,
+        }
+    }
+
+    fn declaration_id(&mut self, declaration: &Rc<RefCell<AstDeclaration>>) -> DeclarationId {
+        let declarations = &mut self.declarations;
+        *self.declaration_ids.entry(Rc::as_ptr(declaration)).or_insert_with(|| {
+            declarations.push(Option::None);
+            DeclarationId(declarations.len() - 1)
+        })
+    }
+
+    fn lower_type(&mut self, type_: &Type) -> HirType {
+        match type_ {
+            Type::Error => panic!("only checked trees without errors can be lowered"),
+            Type::Void => HirType::Void,
+            Type::Bool => HirType::Bool,
+            Type::Char => HirType::Char,
+            Type::String => HirType::String,
+            Type::Number(number) => HirType::Number(*number),
+            Type::Pointer(pointee) => HirType::Pointer(Box::new(self.lower_type(pointee))),
+            Type::Array(length, element) => HirType::Array(*length, Box::new(self.lower_type(element))),
+            Type::Slice(element) => HirType::Slice(Box::new(self.lower_type(element))),
+            Type::Procedure(parameters, return_type) => HirType::Procedure(
+                parameters.iter().map(|parameter| self.lower_type(parameter)).collect(),
+                Box::new(self.lower_type(return_type)),
+            ),
+            Type::Optional(inner) => HirType::Optional(Box::new(self.lower_type(inner))),
+            Type::Struct(declaration) => HirType::Struct(self.declaration_id(declaration)),
+            Type::Enum(declaration) => HirType::Enum(self.declaration_id(declaration)),
+            Type::Type(type_) => HirType::Type(Box::new(self.lower_type(type_))),
+        }
+    }
+
+    fn type_of_expression(&mut self, expression: &AstExpression) -> HirType {
+        let type_ = self.checker.type_of_expression(expression).expect("every expression is checked");
+        self.lower_type(type_)
+    }
+
+    fn lower_declaration(&mut self, declaration: &Rc<RefCell<AstDeclaration>>) -> DeclarationId {
+        let id = self.declaration_id(declaration);
+
+        let type_ = self.checker.type_of_declaration(declaration).expect("every declaration is checked");
+        let type_ = self.lower_type(type_);
+
+        let declaration = declaration.borrow();
+        let value = declaration.value.borrow().as_ref().map(|value| self.lower_expression(value));
+
+        self.declarations[id.0] = Option::Some(HirDeclaration {
+            name: String::from(declaration.name.identifier()),
+            span: declaration.name.span,
+            type_,
+            constant: declaration.constant,
+            value,
+        });
+        id
+    }
+
+    fn lower_block(&mut self, scope: &AstScope) -> HirBlock {
+        HirBlock {
+            statements: scope.statements.iter().map(|statement| self.lower_statement(&statement.borrow())).collect(),
+            value: scope.value.as_ref().map(|value| Box::new(self.lower_expression(&value.borrow()))),
+        }
+    }
+
+    fn loop_id(&mut self, key: *const ()) -> LoopId {
+        let next = LoopId(self.loop_ids.len());
+        *self.loop_ids.entry(key).or_insert(next)
+    }
+
+    /// The loop a `break` or `continue` with the given label leaves, the parser made sure there is one
+    fn target_loop(&mut self, parent_data: &ParentData, label: &Option<Token>) -> LoopId {
+        let scope = parent_data.1.as_ref()
+            .and_then(Weak::upgrade)
+            .expect("break and continue are always inside a scope");
+        let key = match AstScope::enclosing_loop(&scope, label.as_ref()) {
+            Option::Some(AstScopeOwner::While(while_)) => Weak::as_ptr(&while_) as *const (),
+            Option::Some(AstScopeOwner::Loop(loop_)) => Weak::as_ptr(&loop_) as *const (),
+            Option::Some(AstScopeOwner::For(for_)) => Weak::as_ptr(&for_) as *const (),
+            Option::Some(AstScopeOwner::Procedure(_)) |
+            Option::None => panic!("break and continue are always inside their loop"),
+        };
+        self.loop_id(key)
+    }
+
+    fn lower_statement(&mut self, statement: &AstStatement) -> HirStatement {
+        match statement {
+            AstStatement::Expression(expression) => HirStatement::Expression(self.lower_expression(&expression.borrow())),
+
+            AstStatement::Scope(scope) => HirStatement::Block(self.lower_block(&scope.borrow())),
+
+            AstStatement::Declaration(declaration) => HirStatement::Declaration(self.lower_declaration(declaration)),
+
+            AstStatement::Assignment(assignment) => {
+                let assignment = assignment.borrow();
+                let target = self.lower_expression(&assignment.left.borrow());
+                let value = self.lower_expression(&assignment.right.borrow());
+                HirStatement::Assignment {
+                    target,
+                    operator: HirBinaryOperator::from_token(&assignment.operator.kind),
+                    value,
+                }
+            }
+
+            AstStatement::If(if_) => HirStatement::Expression(self.lower_if(&if_.borrow(), HirType::Void)),
+
+            AstStatement::Match(match_) => HirStatement::Expression(self.lower_match(&match_.borrow(), HirType::Void)),
+
+            AstStatement::While(while_) => {
+                let id = self.loop_id(Rc::as_ptr(while_) as *const ());
+                let while_ = while_.borrow();
+                let condition = self.lower_expression(&while_.condition.borrow());
+                let body = self.lower_block(&while_.body.borrow());
+                HirStatement::While { id, condition, body }
+            }
+
+            AstStatement::Loop(loop_) => {
+                let id = self.loop_id(Rc::as_ptr(loop_) as *const ());
+                let body = self.lower_block(&loop_.borrow().body.borrow());
+                HirStatement::Loop { id, body }
+            }
+
+            AstStatement::For(for_) => {
+                let id = self.loop_id(Rc::as_ptr(for_) as *const ());
+                let for_ = for_.borrow();
+                let start = self.lower_expression(&for_.start.borrow());
+                let end = self.lower_expression(&for_.end.borrow());
+                let variable = self.lower_declaration(&for_.variable);
+                let body = self.lower_block(&for_.body.borrow());
+                HirStatement::For { id, variable, start, end, body }
+            }
+
+            AstStatement::Break(break_) => {
+                let break_ = break_.borrow();
+                HirStatement::Break(self.target_loop(&break_.parent_data, &break_.label))
+            }
+
+            AstStatement::Continue(continue_) => {
+                let continue_ = continue_.borrow();
+                HirStatement::Continue(self.target_loop(&continue_.parent_data, &continue_.label))
+            }
+
+            AstStatement::Return(return_) => {
+                HirStatement::Return(return_.borrow().value.as_ref().map(|value| self.lower_expression(&value.borrow())))
+            }
+
+            AstStatement::Error => panic!("only checked trees without errors can be lowered"),
+        }
+    }
+
+    /// Lowers an `if`, `type_` is its type as a whole since only the outermost `if` of a chain is typed
+    fn lower_if(&mut self, if_: &AstIf, type_: HirType) -> HirExpression {
+        let condition = self.lower_expression(&if_.condition.borrow());
+        let then_block = self.lower_block(&if_.then_scope.borrow());
+        let else_block = match &if_.else_ {
+            Option::Some(AstElse::If(else_if)) => Option::Some(HirBlock {
+                statements: Vec::new(),
+                value: Option::Some(Box::new(self.lower_if(&else_if.borrow(), type_.clone()))),
+            }),
+            Option::Some(AstElse::Scope(scope)) => Option::Some(self.lower_block(&scope.borrow())),
+            Option::None => Option::None,
+        };
+
+        HirExpression {
+            kind: HirExpressionKind::If {
+                condition: Box::new(condition),
+                then_block,
+                else_block,
+            },
+            type_,
+            span: if_.span,
+        }
+    }
+
+    fn lower_match(&mut self, match_: &AstMatch, type_: HirType) -> HirExpression {
+        let value = self.lower_expression(&match_.value.borrow());
+        let enum_ = match &value.type_ {
+            HirType::Enum(_) => match self.checker.type_of_expression(&match_.value.borrow()) {
+                Option::Some(Type::Enum(declaration)) => declared_enum(declaration),
+                _ => Option::None,
+            },
+            _ => Option::None,
+        };
+
+        let mut arms = Vec::new();
+        for arm in &match_.arms {
+            let pattern = match &arm.pattern {
+                AstPattern::Wildcard(_) => HirPattern::Wildcard,
+                AstPattern::Variant(pattern) => {
+                    let pattern = pattern.borrow();
+                    let variant = enum_.as_ref()
+                        .and_then(|enum_| enum_.borrow().variants.iter().position(|variant| variant.name.kind == pattern.name.kind))
+                        .expect("variant patterns are checked against the enum");
+                    HirPattern::Variant {
+                        variant,
+                        bindings: pattern.bindings.iter()
+                            .map(|binding| binding.as_ref().map(|binding| self.lower_declaration(binding)))
+                            .collect(),
+                    }
+                }
+                AstPattern::Literal(literal) => HirPattern::Literal(self.lower_expression(&AstExpression::Literal(literal.clone()))),
+            };
+
+            arms.push(HirMatchArm {
+                pattern,
+                body: self.lower_block(&arm.body.borrow()),
+            });
+        }
+
+        HirExpression {
+            kind: HirExpressionKind::Match {
+                value: Box::new(value),
+                arms,
+            },
+            type_,
+            span: match_.span,
+        }
+    }
+
+    /// The enum and index of the variant an `Enum.Variant` expression refers to
+    fn variant_of(&self, expression: &AstExpression) -> Option<(Rc<RefCell<AstDeclaration>>, usize)> {
+        let field = match expression {
+            AstExpression::Field(field) => field.clone(),
+            _ => return Option::None,
+        };
+        let field = field.borrow();
+
+        let declaration = match self.checker.type_of_expression(&field.operand.borrow()) {
+            Option::Some(Type::Type(type_)) => match &**type_ {
+                Type::Enum(declaration) => declaration.clone(),
+                _ => return Option::None,
+            },
+            _ => return Option::None,
+        };
+
+        let enum_ = declared_enum(&declaration)?;
+        let index = enum_.borrow().variants.iter().position(|variant| variant.name.kind == field.name.kind)?;
+        Option::Some((declaration, index))
+    }
+
+    fn lower_expression(&mut self, expression: &AstExpression) -> HirExpression {
+        let type_ = self.type_of_expression(expression);
+        let span = expression.span();
+
+        let kind = match expression {
+            AstExpression::Procedure(procedure) => {
+                let procedure = procedure.borrow();
+                let parameters = procedure.arguments.iter().map(|argument| self.lower_declaration(argument)).collect();
+                let return_type = match &type_ {
+                    HirType::Procedure(_, return_type) => (**return_type).clone(),
+                    _ => unreachable!("procedures have procedure types"),
+                };
+                let body = self.lower_block(&procedure.scope.borrow());
+
+                HirExpressionKind::Procedure(Box::new(HirProcedure {
+                    parameters,
+                    return_type,
+                    body,
+                }))
+            }
+
+            AstExpression::Call(call) => {
+                let call = call.borrow();
+                let callee = call.callee.borrow();
+
+                if let Option::Some((declaration, variant)) = self.variant_of(&callee) {
+                    HirExpressionKind::Variant {
+                        enum_: self.declaration_id(&declaration),
+                        variant,
+                        payload: call.arguments.iter().map(|argument| self.lower_expression(&argument.value.borrow())).collect(),
+                    }
+                } else {
+                    let procedure = match &*callee {
+                        AstExpression::Name(name) => name.borrow().declaration.as_ref()
+                            .and_then(Weak::upgrade)
+                            .and_then(|declaration| declared_procedure(&declaration)),
+                        _ => Option::None,
+                    };
+
+                    let arguments = match procedure {
+                        Option::Some(procedure) => call.bind_arguments(&procedure.borrow()).expect("calls are checked"),
+                        Option::None => call.arguments.iter().map(|argument| Option::Some(argument.value.clone())).collect(),
+                    };
+
+                    HirExpressionKind::Call {
+                        callee: Box::new(self.lower_expression(&callee)),
+                        arguments: arguments.iter()
+                            .map(|argument| argument.as_ref().map(|argument| self.lower_expression(&argument.borrow())))
+                            .collect(),
+                    }
+                }
+            }
+
+            AstExpression::Struct(struct_) => HirExpressionKind::Struct {
+                fields: struct_.borrow().fields.iter().map(|field| self.lower_declaration(field)).collect(),
+            },
+
+            AstExpression::Enum(enum_) => {
+                let mut variants = Vec::new();
+                for variant in &enum_.borrow().variants {
+                    let payload = variant.payload.iter()
+                        .map(|type_| {
+                            let type_ = self.checker.type_of_type(&type_.borrow()).expect("every type is checked");
+                            self.lower_type(type_)
+                        })
+                        .collect();
+                    variants.push(HirVariant {
+                        name: String::from(variant.name.identifier()),
+                        payload,
+                    });
+                }
+                HirExpressionKind::Enum { variants }
+            }
+
+            AstExpression::StructLiteral(literal) => {
+                let literal = literal.borrow();
+                let declaration = match self.checker.type_of_expression(expression) {
+                    Option::Some(Type::Struct(declaration)) => declaration.clone(),
+                    _ => unreachable!("struct literals have struct types"),
+                };
+                let struct_ = declared_struct(&declaration).expect("struct types are declared by a struct");
+                let fields = literal.bind_fields(&struct_.borrow()).expect("struct literals are checked");
+
+                HirExpressionKind::StructLiteral {
+                    struct_: self.declaration_id(&declaration),
+                    fields: fields.iter()
+                        .map(|field| field.as_ref().map(|field| self.lower_expression(&field.borrow())))
+                        .collect(),
+                }
+            }
+
+            AstExpression::Field(field) => {
+                if let Option::Some((declaration, variant)) = self.variant_of(expression) {
+                    let enum_ = self.declaration_id(&declaration);
+                    if let HirType::Procedure(..) = type_ {
+                        HirExpressionKind::VariantConstructor { enum_, variant }
+                    } else {
+                        HirExpressionKind::Variant { enum_, variant, payload: Vec::new() }
+                    }
+                } else {
+                    let field = field.borrow();
+                    let operand = field.operand.borrow();
+                    let index = match self.checker.type_of_expression(&operand) {
+                        Option::Some(Type::Struct(declaration)) => declared_struct(declaration)
+                            .and_then(|struct_| struct_.borrow().fields.iter().position(|member| member.borrow().name.kind == field.name.kind)),
+                        _ => Option::None,
+                    };
+
+                    HirExpressionKind::Field {
+                        operand: Box::new(self.lower_expression(&operand)),
+                        field: index.expect("field access is checked"),
+                    }
+                }
+            }
+
+            AstExpression::ArrayLiteral(array) => HirExpressionKind::Array(
+                array.borrow().elements.iter().map(|element| self.lower_expression(&element.borrow())).collect(),
+            ),
+
+            AstExpression::Index(index) => {
+                let index = index.borrow();
+                let operand = self.lower_expression(&index.operand.borrow());
+                let index = self.lower_expression(&index.index.borrow());
+                HirExpressionKind::Index {
+                    operand: Box::new(operand),
+                    index: Box::new(index),
+                }
+            }
+
+            AstExpression::Slice(slice) => {
+                let slice = slice.borrow();
+                let operand = self.lower_expression(&slice.operand.borrow());
+                HirExpressionKind::Slice {
+                    operand: Box::new(operand),
+                    start: slice.start.as_ref().map(|start| Box::new(self.lower_expression(&start.borrow()))),
+                    end: slice.end.as_ref().map(|end| Box::new(self.lower_expression(&end.borrow()))),
+                }
+            }
+
+            AstExpression::If(if_) => return self.lower_if(&if_.borrow(), type_),
+
+            AstExpression::Match(match_) => return self.lower_match(&match_.borrow(), type_),
+
+            AstExpression::Name(name) => {
+                let name = name.borrow();
+                match name.declaration.as_ref().and_then(Weak::upgrade) {
+                    Option::Some(declaration) => HirExpressionKind::Declaration(self.declaration_id(&declaration)),
+                    Option::None => match name.token.identifier() {
+                        "true" => HirExpressionKind::Bool(true),
+                        "false" => HirExpressionKind::Bool(false),
+                        _ => HirExpressionKind::Type,
+                    },
+                }
+            }
+
+            AstExpression::Literal(literal) => match &literal.borrow().token.kind {
+                // Integer literals can be used as floats
+                TokenKind::Integer(value, _) if matches!(type_, HirType::Number(number) if number.is_float()) => HirExpressionKind::Float(*value as f64),
+                TokenKind::Integer(value, _) => HirExpressionKind::Integer(*value),
+                TokenKind::Float(value, _) => HirExpressionKind::Float(*value),
+                TokenKind::String(value) => HirExpressionKind::String(value.clone()),
+                TokenKind::Char(value) => HirExpressionKind::Char(*value),
+                _ => unreachable!("literals are numbers, strings or characters"),
+            },
+
+            AstExpression::Unary(unary) => {
+                let unary = unary.borrow();
+                let operand = self.lower_expression(&unary.operand.borrow());
+
+                let operator = match unary.operator.kind {
+                    // Unary '+' does nothing
+                    TokenKind::Plus => return HirExpression { span, ..operand },
+                    TokenKind::Minus => HirUnaryOperator::Negate,
+                    TokenKind::ExclamationMark => HirUnaryOperator::Not,
+                    TokenKind::Tilde => HirUnaryOperator::BitwiseNot,
+                    TokenKind::Ampersand => HirUnaryOperator::AddressOf,
+                    TokenKind::Caret => HirUnaryOperator::Dereference,
+                    _ => unreachable!("unary operators are parsed from these tokens"),
+                };
+
+                HirExpressionKind::Unary {
+                    operator,
+                    operand: Box::new(operand),
+                }
+            }
+
+            AstExpression::Binary(binary) => {
+                let binary = binary.borrow();
+                let left = self.lower_expression(&binary.left.borrow());
+                let right = self.lower_expression(&binary.right.borrow());
+                HirExpressionKind::Binary {
+                    operator: HirBinaryOperator::from_token(&binary.operator.kind).expect("binary operators are parsed from these tokens"),
+                    left: Box::new(left),
+                    right: Box::new(right),
+                }
+            }
+
+            AstExpression::Error(_) => panic!("only checked trees without errors can be lowered"),
+        };
+
+        HirExpression {
+            kind,
+            type_,
+            span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::*;
+    use crate::resolver::*;
+
+    fn lower(source: &str) -> Hir {
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file(String::from("test.lang"), String::from(source));
+        let ast = Parser::new(&source_map, file_id).parse().expect("source should parse");
+        Resolver::new().resolve(&ast).expect("source should resolve");
+        let mut checker = Checker::new();
+        checker.check(&ast).expect("source should check");
+        Lowerer::new(&checker).lower(&ast)
+    }
+
+    fn declared(hir: &Hir, name: &str) -> DeclarationId {
+        let index = hir.declarations.iter().position(|declaration| declaration.name == name).expect("name should be declared");
+        DeclarationId(index)
+    }
+
+    fn value<'a>(hir: &'a Hir, name: &str) -> &'a HirExpression {
+        hir.declaration(declared(hir, name)).value.as_ref().expect("declaration should have a value")
+    }
+
+    fn procedure<'a>(hir: &'a Hir, name: &str) -> &'a HirProcedure {
+        match &value(hir, name).kind {
+            HirExpressionKind::Procedure(procedure) => procedure,
+            kind => panic!("expected a procedure, got {:?}", kind),
+        }
+    }
+
+    fn arguments<'a>(hir: &'a Hir, name: &str) -> &'a [Option<HirExpression>] {
+        match &value(hir, name).kind {
+            HirExpressionKind::Call { arguments, .. } => arguments,
+            kind => panic!("expected a call, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn declaration_ids() {
+        let hir = lower("add :: (a: int, b: int) -> int { a + b } x := add(1, 2);");
+        let (add, a, b, x) = (declared(&hir, "add"), declared(&hir, "a"), declared(&hir, "b"), declared(&hir, "x"));
+
+        assert!(matches!(hir.statements[..], [HirStatement::Declaration(first), HirStatement::Declaration(second)] if first == add && second == x));
+
+        let procedure = procedure(&hir, "add");
+        assert_eq!(procedure.parameters, [a, b]);
+        assert_eq!(hir.declaration(a).type_, HirType::Number(NumberSuffix::I64));
+        match &procedure.body.value.as_ref().expect("body should have a value").kind {
+            HirExpressionKind::Binary { operator: HirBinaryOperator::Add, left, right } => {
+                assert!(matches!(left.kind, HirExpressionKind::Declaration(id) if id == a));
+                assert!(matches!(right.kind, HirExpressionKind::Declaration(id) if id == b));
+            }
+            kind => panic!("expected an addition, got {:?}", kind),
+        }
+
+        match &value(&hir, "x").kind {
+            HirExpressionKind::Call { callee, .. } => assert!(matches!(callee.kind, HirExpressionKind::Declaration(id) if id == add)),
+            kind => panic!("expected a call, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn default_arguments() {
+        let hir = lower("
+            add :: (a: int, b: int = 1) -> int { a + b }
+            x := add(2);
+            y := add(b = 3, a = 4);
+        ");
+
+        let x = arguments(&hir, "x");
+        assert!(matches!(x, [Option::Some(HirExpression { kind: HirExpressionKind::Integer(2), .. }), Option::None]));
+
+        let y = arguments(&hir, "y");
+        assert!(matches!(y, [
+            Option::Some(HirExpression { kind: HirExpressionKind::Integer(4), .. }),
+            Option::Some(HirExpression { kind: HirExpressionKind::Integer(3), .. }),
+        ]));
+
+        // The default stays on the parameter for backends to use
+        let b = hir.declaration(declared(&hir, "b"));
+        assert!(matches!(b.value, Option::Some(HirExpression { kind: HirExpressionKind::Integer(1), .. })));
+    }
+
+    /// Lists every loop and the loop each 'break' and 'continue' targets, in order
+    fn loop_targets(block: &HirBlock, targets: &mut Vec<String>) {
+        for statement in &block.statements {
+            match statement {
+                HirStatement::While { id, body, .. } |
+                HirStatement::Loop { id, body } |
+                HirStatement::For { id, body, .. } => {
+                    targets.push(format!("loop {}", id.0));
+                    loop_targets(body, targets);
+                }
+                HirStatement::Break(id) => targets.push(format!("break {}", id.0)),
+                HirStatement::Continue(id) => targets.push(format!("continue {}", id.0)),
+                HirStatement::Block(block) => loop_targets(block, targets),
+                HirStatement::Expression(HirExpression { kind: HirExpressionKind::If { then_block, else_block, .. }, .. }) => {
+                    loop_targets(then_block, targets);
+                    if let Option::Some(else_block) = else_block {
+                        loop_targets(else_block, targets);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn loop_targets_with_and_without_labels() {
+        let hir = lower("
+            main :: () {
+                outer: while true {
+                    for i in 0..3 {
+                        if i == 1 { continue; }
+                        if i == 2 { break outer; }
+                        break;
+                    }
+                    inner: loop {
+                        { continue outer; }
+                        break inner;
+                    }
+                    break;
+                }
+            }
+        ");
+
+        let mut targets = Vec::new();
+        loop_targets(&procedure(&hir, "main").body, &mut targets);
+        assert_eq!(targets, [
+            "loop 0",
+            "loop 1", "continue 1", "break 0", "break 1",
+            "loop 2", "continue 0", "break 2",
+            "break 0",
+        ]);
+    }
+}
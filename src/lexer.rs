@@ -1,51 +1,275 @@
 pub use crate::token::*;
+use unicode_normalization::UnicodeNormalization;
 
 pub struct Lexer {
-    source: Vec<char>,
+    file_id: FileId,
+    source: String,
+    /// Byte offset into `source`
     position: usize,
-    line: usize,
-    column: usize,
 }
 
 impl Lexer {
-    pub fn new(source: String) -> Lexer {
+    pub fn new(file_id: FileId, source: String) -> Lexer {
         Lexer {
-            source: source.chars().collect(),
+            file_id,
+            source,
             position: 0,
-            line: 1,
-            column: 1,
         }
     }
 
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.file_id, start, end)
+    }
+
     fn current(&self) -> char {
-        *self.source.get(self.position).unwrap_or(&'\0')
+        self.peek(0)
+    }
+
+    fn peek(&self, offset: usize) -> char {
+        self.source[self.position..].chars().nth(offset).unwrap_or('\0')
     }
 
     fn next_char(&mut self) -> char {
-        let current = &self.current();
-        self.position += 1;
-        self.column += 1;
-        if current == &'\n' {
-            self.line += 1;
-            self.column = 1;
+        let current = self.current();
+        self.position += current.len_utf8();
+        current
+    }
+
+    fn is_raw_string_start(&self) -> bool {
+        let mut offset = 1;
+        while self.peek(offset) == '#' {
+            offset += 1;
+        }
+        self.current() == 'r' && self.peek(offset) == '"'
+    }
+
+    /// Lexes an escape sequence starting at the current '\\',
+    /// on failure returns an error token spanning the bad escape
+    fn escape_sequence(&mut self) -> Result<char, Token> {
+        let start_position = self.position;
+
+        macro_rules! error {
+            ($message:expr) => {{
+                return Result::Err(Token::new(
+                    TokenKind::Error(String::from($message)),
+                    self.span(start_position, self.position),
+                ));
+            }};
+        }
+
+        self.next_char();
+        let escaped = match self.current() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+
+            'u' => {
+                self.next_char();
+                if self.current() != '{' {
+                    error!("Expected '{' after '\\u'");
+                }
+                self.next_char();
+
+                let mut digits = String::new();
+                while self.current().is_ascii_hexdigit() {
+                    digits.push(self.next_char());
+                }
+
+                if self.current() != '}' {
+                    error!("Unterminated unicode escape");
+                }
+                self.next_char();
+
+                if digits.is_empty() || digits.len() > 6 {
+                    error!("Unicode escape must have between 1 and 6 hex digits");
+                }
+
+                return match char::from_u32(u32::from_str_radix(&digits, 16).unwrap()) {
+                    Option::Some(chr) => Result::Ok(chr),
+                    Option::None => error!("Invalid unicode character in escape"),
+                };
+            }
+
+            '\0' | '\n' => error!("Unknown escape sequence"),
+
+            _ => {
+                self.next_char();
+                error!("Unknown escape sequence");
+            }
+        };
+        self.next_char();
+
+        Result::Ok(escaped)
+    }
+
+    /// Consumes the digits of a number literal in the given base, skipping '_' separators
+    fn digits(&mut self, base: u32, text: &mut String) -> Result<(), Token> {
+        loop {
+            match self.current() {
+                '_' => {
+                    self.next_char();
+                }
+
+                chr if chr.is_digit(base) => text.push(self.next_char()),
+
+                chr if chr.is_ascii_digit() => {
+                    return Result::Err(Token::new(
+                        TokenKind::Error(format!("Digit greater than base {}", base)),
+                        self.span(self.position, self.position + 1),
+                    ));
+                }
+
+                _ => return Result::Ok(()),
+            }
+        }
+    }
+
+    /// Skips whatever is left of a malformed number literal so it isn't lexed as more tokens
+    fn skip_number_tail(&mut self) {
+        while self.current().is_ascii_alphanumeric() || self.current() == '_' || (self.current() == '.' && self.peek(1).is_ascii_digit()) {
+            self.next_char();
+        }
+    }
+
+    fn number(&mut self) -> Token {
+        let start_position = self.position;
+
+        macro_rules! error {
+            ($message:expr, $start:expr) => {{
+                let (message, start) = ($message, $start);
+                let span = self.span(start, self.position.max(start + 1));
+                self.skip_number_tail();
+                return Token::new(TokenKind::Error(message), span);
+            }};
+        }
+
+        macro_rules! try_digits {
+            ($base:expr, $text:expr) => {{
+                if let Result::Err(token) = self.digits($base, $text) {
+                    self.skip_number_tail();
+                    return token;
+                }
+            }};
+        }
+
+        let base = match (self.current(), self.peek(1)) {
+            ('0', 'x' | 'X') => 16,
+            ('0', 'o' | 'O') => 8,
+            ('0', 'b' | 'B') => 2,
+            _ => 10,
+        };
+        if base != 10 {
+            self.next_char();
+            self.next_char();
+        }
+
+        let mut text = String::new();
+        try_digits!(base, &mut text);
+
+        if text.is_empty() {
+            error!(String::from("Expected digits after base prefix"), start_position);
+        }
+
+        let mut is_float = false;
+
+        // '0..n' is a range, not the float '0.'
+        if self.current() == '.' && self.peek(1) != '.' {
+            if base != 10 {
+                error!(String::from("Float literal must be base 10"), self.position);
+            }
+
+            is_float = true;
+            text.push(self.next_char());
+            try_digits!(10, &mut text);
+
+            if self.current() == '.' && self.peek(1) != '.' {
+                error!(String::from("Cannot have multiple '.' in float literal"), self.position);
+            }
+        }
+
+        if base == 10 && (self.current() == 'e' || self.current() == 'E') {
+            let exponent_position = self.position;
+
+            is_float = true;
+            self.next_char();
+            text.push('e');
+
+            if self.current() == '+' || self.current() == '-' {
+                text.push(self.next_char());
+            }
+
+            if !self.current().is_ascii_digit() {
+                error!(String::from("Expected digits in float exponent"), exponent_position);
+            }
+            try_digits!(10, &mut text);
+        }
+
+        let suffix_position = self.position;
+
+        let suffix = if self.current().is_ascii_alphabetic() {
+            let mut name = String::new();
+            while self.current().is_ascii_alphanumeric() || self.current() == '_' {
+                name.push(self.next_char());
+            }
+
+            match NumberSuffix::from_name(&name) {
+                Option::Some(suffix) => Option::Some(suffix),
+                Option::None => error!(format!("Invalid suffix '{}' for number literal", name), suffix_position),
+            }
+        } else {
+            Option::None
+        };
+
+        if let Option::Some(suffix) = suffix {
+            if is_float && !suffix.is_float() {
+                error!(format!("Cannot use integer suffix '{}' on a float literal", suffix), suffix_position);
+            }
+
+            if base != 10 && suffix.is_float() {
+                error!(format!("Float suffix '{}' requires a base 10 literal", suffix), suffix_position);
+            }
+
+            is_float |= suffix.is_float();
         }
-        *current
+
+        let kind = if is_float {
+            TokenKind::Float(text.parse().unwrap(), suffix)
+        } else if let Result::Ok(value) = u64::from_str_radix(&text, base) {
+            if let Option::Some(suffix) = suffix {
+                // The minimum of a signed type is written without a suffix, e.g. 'x: i8 = -128;'
+                if value > suffix.max_literal(false) {
+                    error!(format!("Integer literal is out of range for '{}'", suffix), start_position);
+                }
+            }
+            TokenKind::Integer(value, suffix)
+        } else {
+            error!(String::from("Integer literal is too large to fit in 64 bits"), start_position);
+        };
+
+        Token::new(kind, self.span(start_position, self.position))
+    }
+
+    /// Lexes the next token without consuming it
+    pub fn peek_token(&mut self) -> Token {
+        let position = self.position;
+        let token = self.next_token();
+        self.position = position;
+        token
     }
 
     pub fn next_token(&mut self) -> Token {
         loop {
             let start_position = self.position;
-            let start_line = self.line;
-            let start_column = self.column;
 
             macro_rules! token {
                 ($kind:expr) => {{
                     return Token::new(
                         $kind,
-                        start_position,
-                        start_line,
-                        start_column,
-                        self.position - start_position,
+                        self.span(start_position, self.position),
                     );
                 }};
             }
@@ -87,162 +311,416 @@ impl Lexer {
                 ')' => match_token!(TokenKind::RParen),
                 '{' => match_token!(TokenKind::LBrace),
                 '}' => match_token!(TokenKind::RBrace),
+                '[' => match_token!(TokenKind::LBracket),
+                ']' => match_token!(TokenKind::RBracket),
                 ',' => match_token!(TokenKind::Comma),
+                '?' => match_token!(TokenKind::QuestionMark),
+                '.' => match_token!(TokenKind::Dot, '.', TokenKind::DotDot),
 
                 '+' => match_token!(TokenKind::Plus, '=', TokenKind::PlusEquals),
                 '-' => match_token!(TokenKind::Minus, '=', TokenKind::MinusEquals, '>', TokenKind::RightArrow),
                 '*' => match_token!(TokenKind::Asterisk, '=', TokenKind::AsteriskEquals),
+                '/' if self.peek(1) == '/' => {
+                    self.next_char();
+                    self.next_char();
+
+                    // '///' is a doc comment, but '////' is just a regular comment
+                    let is_doc_comment = self.current() == '/' && self.peek(1) != '/';
+                    if is_doc_comment {
+                        self.next_char();
+                        if self.current() == ' ' {
+                            self.next_char();
+                        }
+                    }
+
+                    let mut text = String::new();
+                    while self.current() != '\n' && self.current() != '\0' {
+                        text.push(self.next_char());
+                    }
+
+                    if is_doc_comment {
+                        if text.ends_with('\r') {
+                            text.pop();
+                        }
+                        token!(TokenKind::DocComment(text))
+                    }
+                    continue;
+                }
+
+                '/' if self.peek(1) == '*' => {
+                    self.next_char();
+                    self.next_char();
+
+                    let mut depth = 1;
+                    while depth > 0 {
+                        if self.current() == '\0' {
+                            return Token::new(
+                                TokenKind::Error(String::from("Unterminated block comment")),
+                                self.span(start_position, start_position + 2),
+                            );
+                        } else if self.current() == '/' && self.peek(1) == '*' {
+                            self.next_char();
+                            self.next_char();
+                            depth += 1;
+                        } else if self.current() == '*' && self.peek(1) == '/' {
+                            self.next_char();
+                            self.next_char();
+                            depth -= 1;
+                        } else {
+                            self.next_char();
+                        }
+                    }
+                    continue;
+                }
+
                 '/' => match_token!(TokenKind::Slash, '=', TokenKind::SlashEquals),
                 '%' => match_token!(TokenKind::Percent, '=', TokenKind::PercentEquals),
-                '=' => match_token!(TokenKind::Equals, '=', TokenKind::EqualsEquals),
+                '=' => match_token!(TokenKind::Equals, '=', TokenKind::EqualsEquals, '>', TokenKind::FatArrow),
                 '!' => match_token!(TokenKind::ExclamationMark, '=', TokenKind::ExclamationMarkEquals),
+                '&' => match_token!(TokenKind::Ampersand, '&', TokenKind::AmpersandAmpersand, '=', TokenKind::AmpersandEquals),
+                '|' => match_token!(TokenKind::Pipe, '|', TokenKind::PipePipe, '=', TokenKind::PipeEquals),
+                '^' => match_token!(TokenKind::Caret, '=', TokenKind::CaretEquals),
+                '~' => match_token!(TokenKind::Tilde),
+
+                '<' => {
+                    self.next_char();
+                    match self.current() {
+                        '=' => match_token!(TokenKind::LessThanEquals),
+                        '<' => match_token!(TokenKind::LessThanLessThan, '=', TokenKind::LessThanLessThanEquals),
+                        _ => token!(TokenKind::LessThan),
+                    }
+                }
+
+                '>' => {
+                    self.next_char();
+                    match self.current() {
+                        '=' => match_token!(TokenKind::GreaterThanEquals),
+                        '>' => match_token!(TokenKind::GreaterThanGreaterThan, '=', TokenKind::GreaterThanGreaterThanEquals),
+                        _ => token!(TokenKind::GreaterThan),
+                    }
+                }
 
                 ' ' | '\n' | '\r' | '\t' => {
                     self.next_char();
                     continue;
                 }
 
-                // TODO: Allow any utf8 letter?
-                'A'..='Z' | 'a'..='z' | '_' => {
-                    let mut identifier = String::new();
+                '"' => {
+                    self.next_char();
 
+                    let mut value = String::new();
+                    let mut error = Option::None;
                     loop {
                         match self.current() {
-                            'A'..='Z' | 'a'..='z' | '0'..='9' | '_' => {
-                                identifier.push(self.next_char());
+                            '\0' => {
+                                return Token::new(
+                                    TokenKind::Error(String::from("Unterminated string literal")),
+                                    self.span(start_position, start_position + 1),
+                                );
                             }
 
-                            _ => break,
-                        }
-                    }
+                            '"' => {
+                                self.next_char();
+                                break;
+                            }
 
-                    token!(TokenKind::Identifier(identifier))
-                }
+                            '\\' => match self.escape_sequence() {
+                                Result::Ok(chr) => value.push(chr),
+                                Result::Err(token) => {
+                                    // Keep going so the rest of the string isn't lexed as code
+                                    error.get_or_insert(token);
+                                }
+                            },
 
-                '0'..='9' => {
-                    fn char_to_int(chr: char) -> u64 {
-                        match chr {
-                            '0'..='9' => chr as u64 - '0' as u64,
-                            'A'..='Z' => chr as u64 - 'A' as u64,
-                            'a'..='z' => chr as u64 - 'a' as u64,
-                            _ => panic!("Unexpected character"),
+                            _ => value.push(self.next_char()),
                         }
                     }
 
-                    let mut int_value = 0;
-
-                    if self.current() == '0' {
-                        self.next_char();
+                    if let Option::Some(error) = error {
+                        return error;
                     }
+                    token!(TokenKind::String(value))
+                }
 
-                    let base = match self.current() {
-                        'x' | 'X' => {
-                            self.next_char();
-                            16
-                        }
-
-                        'b' | 'B' => {
-                            self.next_char();
-                            2
-                        }
+                'r' if self.is_raw_string_start() => {
+                    self.next_char();
 
-                        _ => 10,
-                    };
+                    let mut hashes = 0;
+                    while self.current() == '#' {
+                        self.next_char();
+                        hashes += 1;
+                    }
+                    self.next_char();
 
+                    let mut value = String::new();
                     loop {
                         match self.current() {
-                            '0'..='9' | 'A'..='Z' | 'a'..='z' => {
-                                let value = char_to_int(self.current());
-
-                                if value >= base {
-                                    return Token::new(
-                                        TokenKind::Error(String::from("Digit grater than base")),
-                                        self.position,
-                                        self.line,
-                                        self.column,
-                                        1,
-                                    );
-                                }
-
-                                int_value *= base;
-                                int_value += value;
-
-                                self.next_char();
+                            '\0' => {
+                                return Token::new(
+                                    TokenKind::Error(String::from("Unterminated raw string literal")),
+                                    self.span(start_position, start_position + hashes + 2),
+                                );
                             }
 
-                            '.' => {
-                                if base != 10 {
-                                    return Token::new(
-                                        TokenKind::Error(String::from("Float literal must be base 10")),
-                                        self.position,
-                                        self.line,
-                                        self.column,
-                                        1,
-                                    );
+                            '"' if (1..=hashes).all(|offset| self.peek(offset) == '#') => {
+                                for _ in 0..=hashes {
+                                    self.next_char();
                                 }
+                                break;
+                            }
 
-                                self.next_char();
+                            _ => value.push(self.next_char()),
+                        }
+                    }
 
-                                let mut float_value = int_value as f64;
-                                let mut denominator = 1;
-
-                                loop {
-                                    match self.current() {
-                                        '0'..='9' | 'A'..='Z' | 'a'..='z' => {
-                                            let value = char_to_int(self.current());
-
-                                            if value >= base {
-                                                return Token::new(
-                                                    TokenKind::Error(String::from("Digit grater than base")),
-                                                    self.position,
-                                                    self.line,
-                                                    self.column,
-                                                    1,
-                                                );
-                                            }
-
-                                            denominator *= base;
-                                            float_value += value as f64 / denominator as f64;
-
-                                            self.next_char();
-                                        }
-
-                                        '.' => {
-                                            return Token::new(
-                                                TokenKind::Error(String::from("Cannot have multiple '.' in float literal")),
-                                                self.position,
-                                                self.line,
-                                                self.column,
-                                                1,
-                                            );
-                                        }
-
-                                        '_' => {
-                                            self.next_char();
-                                            continue;
-                                        }
-
-                                        _ => break,
-                                    }
-                                }
+                    token!(TokenKind::String(value))
+                }
 
-                                token!(TokenKind::Float(float_value))
-                            }
+                '\'' => {
+                    self.next_char();
 
-                            '_' => {
-                                self.next_char();
-                                continue;
+                    let value = match self.current() {
+                        '\\' => match self.escape_sequence() {
+                            Result::Ok(chr) => chr,
+                            Result::Err(token) => {
+                                while !matches!(self.current(), '\'' | '\n' | '\0') {
+                                    self.next_char();
+                                }
+                                if self.current() == '\'' {
+                                    self.next_char();
+                                }
+                                return token;
                             }
+                        },
+
+                        '\'' => {
+                            self.next_char();
+                            token!(TokenKind::Error(String::from("Empty character literal")))
+                        }
+
+                        '\n' | '\0' => {
+                            return Token::new(
+                                TokenKind::Error(String::from("Unterminated character literal")),
+                                self.span(start_position, start_position + 1),
+                            );
+                        }
 
-                            _ => break,
+                        _ => self.next_char(),
+                    };
+
+                    if self.current() != '\'' {
+                        while !matches!(self.current(), '\'' | '\n' | '\0') {
+                            self.next_char();
                         }
+                        if self.current() == '\'' {
+                            self.next_char();
+                            token!(TokenKind::Error(String::from("Character literal must contain exactly one character")))
+                        }
+                        return Token::new(
+                            TokenKind::Error(String::from("Unterminated character literal")),
+                            self.span(start_position, start_position + 1),
+                        );
                     }
+                    self.next_char();
 
-                    token!(TokenKind::Integer(int_value))
+                    token!(TokenKind::Char(value))
                 }
 
+                chr if chr == '_' || unicode_ident::is_xid_start(chr) => {
+                    let mut identifier = String::new();
+
+                    while unicode_ident::is_xid_continue(self.current()) {
+                        identifier.push(self.next_char());
+                    }
+
+                    match identifier.as_str() {
+                        "if" => token!(TokenKind::If),
+                        "else" => token!(TokenKind::Else),
+                        "struct" => token!(TokenKind::Struct),
+                        "enum" => token!(TokenKind::Enum),
+                        "match" => token!(TokenKind::Match),
+                        "while" => token!(TokenKind::While),
+                        "loop" => token!(TokenKind::Loop),
+                        "for" => token!(TokenKind::For),
+                        "in" => token!(TokenKind::In),
+                        "break" => token!(TokenKind::Break),
+                        "continue" => token!(TokenKind::Continue),
+                        "return" => token!(TokenKind::Return),
+                        // Normalize so that visually identical names compare equal
+                        _ => token!(TokenKind::Identifier(identifier.nfc().collect())),
+                    }
+                }
+
+                '0'..='9' => return self.number(),
+
                 _ => match_token!(TokenKind::Error(String::from("Unknown character"))),
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Token {
+        Lexer::new(FileId(0), String::from(source)).next_token()
+    }
+
+    /// Every token kind up to the end of the file
+    fn lex_all(source: &str) -> Vec<TokenKind> {
+        let mut lexer = Lexer::new(FileId(0), String::from(source));
+        let mut kinds = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if token.kind == TokenKind::EndOfFile {
+                return kinds;
+            }
+            kinds.push(token.kind);
+        }
+    }
+
+    fn identifier(name: &str) -> TokenKind {
+        TokenKind::Identifier(String::from(name))
+    }
+
+    fn assert_error(source: &str, message: &str, start: usize, end: usize) {
+        let token = lex(source);
+        assert_eq!(token.kind, TokenKind::Error(String::from(message)));
+        assert_eq!(token.span, Span::new(FileId(0), start, end));
+    }
+
+    #[test]
+    fn hex_literal() {
+        assert_eq!(lex("0xFF").kind, TokenKind::Integer(255, Option::None));
+        assert_eq!(lex("0xff").kind, TokenKind::Integer(255, Option::None));
+        assert_eq!(lex("0xA_b").kind, TokenKind::Integer(0xAB, Option::None));
+    }
+
+    #[test]
+    fn octal_and_binary_literals() {
+        assert_eq!(lex("0o17").kind, TokenKind::Integer(15, Option::None));
+        assert_eq!(lex("0b101").kind, TokenKind::Integer(5, Option::None));
+    }
+
+    #[test]
+    fn integer_overflow() {
+        assert_eq!(lex("18446744073709551615").kind, TokenKind::Integer(u64::MAX, Option::None));
+        assert_error("18446744073709551616", "Integer literal is too large to fit in 64 bits", 0, 20);
+        assert_error("0x1_0000_0000_0000_0000", "Integer literal is too large to fit in 64 bits", 0, 23);
+    }
+
+    #[test]
+    fn float_exponent() {
+        assert_eq!(lex("1.5e10").kind, TokenKind::Float(1.5e10, Option::None));
+        assert_eq!(lex("2E-3").kind, TokenKind::Float(2e-3, Option::None));
+        assert_error("1.5e", "Expected digits in float exponent", 3, 4);
+    }
+
+    #[test]
+    fn error_spans() {
+        assert_error("0b102", "Digit greater than base 2", 4, 5);
+        assert_error("0x", "Expected digits after base prefix", 0, 2);
+        assert_error("0x1.5", "Float literal must be base 10", 3, 4);
+        assert_error("1.2.3", "Cannot have multiple '.' in float literal", 3, 4);
+        assert_error("12abc", "Invalid suffix 'abc' for number literal", 2, 5);
+    }
+
+    #[test]
+    fn signed_suffix_range() {
+        assert_eq!(lex("127i8").kind, TokenKind::Integer(127, Option::Some(NumberSuffix::I8)));
+        assert_error("128i8", "Integer literal is out of range for 'i8'", 0, 5);
+        assert_eq!(lex("255u8").kind, TokenKind::Integer(255, Option::Some(NumberSuffix::U8)));
+    }
+
+    #[test]
+    fn line_comments() {
+        assert_eq!(lex_all("a // b c\nd"), [identifier("a"), identifier("d")]);
+        assert_eq!(lex_all("a // b"), [identifier("a")]);
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        assert_eq!(lex_all("a /* b /* c */ d */ e"), [identifier("a"), identifier("e")]);
+        assert_eq!(lex_all("a /**/ b"), [identifier("a"), identifier("b")]);
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        assert_error("/* a /* b */", "Unterminated block comment", 0, 2);
+
+        let mut lexer = Lexer::new(FileId(0), String::from("x /* y"));
+        assert_eq!(lexer.next_token().kind, identifier("x"));
+        let token = lexer.next_token();
+        assert_eq!(token.kind, TokenKind::Error(String::from("Unterminated block comment")));
+        assert_eq!(token.span, Span::new(FileId(0), 2, 4));
+    }
+
+    #[test]
+    fn doc_comments() {
+        assert_eq!(lex_all("/// Adds two numbers\nadd"), [TokenKind::DocComment(String::from("Adds two numbers")), identifier("add")]);
+        assert_eq!(lex_all("///no space\r\n"), [TokenKind::DocComment(String::from("no space"))]);
+        assert_eq!(lex_all("//// not a doc comment\nadd"), [identifier("add")]);
+    }
+
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(lex(r#""a\nb\tc\\d\"e""#).kind, TokenKind::String(String::from("a\nb\tc\\d\"e")));
+        assert_eq!(lex(r#""\u{48}\u{1F600}""#).kind, TokenKind::String(String::from("H\u{1F600}")));
+    }
+
+    #[test]
+    fn char_escapes() {
+        assert_eq!(lex(r"'\n'").kind, TokenKind::Char('\n'));
+        assert_eq!(lex(r"'\''").kind, TokenKind::Char('\''));
+        assert_eq!(lex(r"'\u{e9}'").kind, TokenKind::Char('é'));
+    }
+
+    #[test]
+    fn raw_strings() {
+        assert_eq!(lex(r#"r"a\nb""#).kind, TokenKind::String(String::from(r"a\nb")));
+        assert_eq!(lex(r##"r#"a "b" c"#"##).kind, TokenKind::String(String::from(r#"a "b" c"#)));
+        assert_eq!(lex(r###"r##"a"#b"##"###).kind, TokenKind::String(String::from(r##"a"#b"##)));
+    }
+
+    #[test]
+    fn unterminated_literals() {
+        assert_error(r#""abc"#, "Unterminated string literal", 0, 1);
+        assert_error(r##"r#"abc"##, "Unterminated raw string literal", 0, 3);
+        assert_error(r###"r##"a"#"###, "Unterminated raw string literal", 0, 4);
+        assert_error("'a", "Unterminated character literal", 0, 1);
+        assert_error("'\n'", "Unterminated character literal", 0, 1);
+    }
+
+    #[test]
+    fn bad_escapes() {
+        assert_error(r#""a\qb""#, "Unknown escape sequence", 2, 4);
+        assert_error(r"'\q'", "Unknown escape sequence", 1, 3);
+        assert_error(r#""\u{110000}""#, "Invalid unicode character in escape", 1, 11);
+        assert_error(r#""\u{}""#, "Unicode escape must have between 1 and 6 hex digits", 1, 5);
+
+        // The rest of the string is still skipped
+        assert_eq!(lex_all(r#""\q a" b"#), [TokenKind::Error(String::from("Unknown escape sequence")), identifier("b")]);
+    }
+
+
+    #[test]
+    fn identifiers_are_normalized() {
+        let composed = lex("caf\u{E9}");
+        let decomposed = lex("cafe\u{301}");
+        assert_eq!(composed.kind, identifier("caf\u{E9}"));
+        assert_eq!(composed.kind, decomposed.kind);
+    }
+
+    #[test]
+    fn spans_are_byte_offsets() {
+        let mut lexer = Lexer::new(FileId(0), String::from("cafe\u{301} := \u{3C0};"));
+        let spans: Vec<_> = std::iter::from_fn(|| Option::Some(lexer.next_token()))
+            .take_while(|token| token.kind != TokenKind::EndOfFile)
+            .map(|token| (token.span.start, token.span.end))
+            .collect();
+        // 'e' + U+0301 is 3 bytes and U+03C0 is 2 bytes
+        assert_eq!(spans, [(0, 6), (7, 8), (8, 9), (10, 12), (12, 13)]);
+    }
+}
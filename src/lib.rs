@@ -0,0 +1,9 @@
+pub mod source_map;
+pub mod token;
+pub mod diagnostic;
+pub mod lexer;
+pub mod ast;
+pub mod parser;
+pub mod resolver;
+pub mod checker;
+pub mod hir;
@@ -1,40 +1,67 @@
-mod token;
-mod lexer;
-mod ast;
-mod parser;
-
-use crate::lexer::*;
-use crate::parser::*;
+use lang::lexer::*;
+use lang::parser::*;
+use lang::resolver::*;
+use lang::checker::*;
+use lang::hir::*;
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
-    assert!(args.len() > 0);
+    assert!(!args.is_empty());
 
-    if args.len() != 2 {
-        println!("usage: {} file", args[0]);
+    if args.len() < 2 {
+        println!("usage: {} file...", args[0]);
         return;
     }
 
-    let source = if let Ok(source) = std::fs::read_to_string(&args[1]) {
-        source
-    } else {
-        panic!("Unable to open '{}'", args[1]);
-    };
+    let mut source_map = SourceMap::new();
+    let mut file_ids = Vec::new();
+    for path in &args[1..] {
+        match source_map.load(path) {
+            Result::Ok(file_id) => file_ids.push(file_id),
+            Result::Err(error) => {
+                eprintln!("error: Unable to open '{}': {}", path, error);
+                std::process::exit(1);
+            }
+        }
+    }
 
-    let mut lexer = Lexer::new(source);
-    loop {
-        let token = lexer.next_token();
+    let mut failed = false;
+    for &file_id in &file_ids {
+        let mut lexer = Lexer::new(file_id, source_map.file(file_id).source.clone());
+        loop {
+            let token = lexer.next_token();
 
-        println!("{:?}", token);
+            println!("{:?}", token);
+
+            if token.kind == TokenKind::EndOfFile {
+                break;
+            }
+        }
 
-        match token.kind {
-            TokenKind::Error(_) => panic!("Error: {:?}", token),
-            TokenKind::EndOfFile => break,
-            _ => {}
+        let mut parser = Parser::new(&source_map, file_id);
+        let (file_ast, diagnostics) = parser.parse_partial();
+        for diagnostic in &diagnostics {
+            eprintln!("{}", diagnostic.render(&source_map));
+        }
+        if diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+            failed = true;
+            continue;
+        }
+
+        let mut resolver = Resolver::new();
+        let mut checker = Checker::new();
+        match resolver.resolve(&file_ast).and_then(|()| checker.check(&file_ast)) {
+            Result::Ok(()) => println!("{:#?}", Lowerer::new(&checker).lower(&file_ast)),
+            Result::Err(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    eprintln!("{}", diagnostic.render(&source_map));
+                }
+                failed = true;
+            }
         }
     }
 
-    let mut parser = Parser::new(&args[1]);
-    let file_ast = parser.parse();
-    println!("{:#?}", file_ast);
+    if failed {
+        std::process::exit(1);
+    }
 }
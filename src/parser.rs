@@ -1,48 +1,153 @@
 pub use crate::ast::*;
+pub use crate::diagnostic::*;
 use crate::lexer::*;
 
 pub struct Parser {
-    file_path: String,
-    source: String,
+    file_id: FileId,
     lexer: Lexer,
     current: Token,
+    previous: Token,
+    diagnostics: Vec<Diagnostic>,
+    doc_comments: Vec<String>,
+    /// Labels of the loops around the statement being parsed, innermost last
+    loop_labels: Vec<Option<Token>>,
+    /// The return type of the procedure being parsed, `None` outside of procedures
+    return_type: Option<Option<AstType>>,
+    /// Cleared while parsing an expression that is followed by a `{ }` block (e.g. the condition of an `if`),
+    /// where `name {` starts the block rather than a struct literal
+    struct_literals: bool,
 }
 
 impl Parser {
-    pub fn new(path: &str) -> Parser {
-        let source = if let Ok(source) = std::fs::read_to_string(path) {
-            source
-        } else {
-            panic!("Unable to open '{}'", path);
+    pub fn new(source_map: &SourceMap, file_id: FileId) -> Parser {
+        let mut parser = Parser {
+            file_id,
+            lexer: Lexer::new(file_id, source_map.file(file_id).source.clone()),
+            current: Token::new(TokenKind::EndOfFile, Span::new(file_id, 0, 0)),
+            previous: Token::new(TokenKind::EndOfFile, Span::new(file_id, 0, 0)),
+            diagnostics: Vec::new(),
+            doc_comments: Vec::new(),
+            loop_labels: Vec::new(),
+            return_type: Option::None,
+            struct_literals: true,
         };
+        parser.next_token();
+        parser
+    }
 
-        let mut lexer = Lexer::new(source.clone());
-        Parser {
-            file_path: String::from(path),
-            source,
-            current: lexer.next_token(),
-            lexer,
+    fn next_token(&mut self) -> Token {
+        let mut next = self.lexer.next_token();
+        // Doc comments are collected here and attached to the next declaration
+        while let TokenKind::DocComment(text) = next.kind {
+            self.doc_comments.push(text);
+            next = self.lexer.next_token();
         }
+        self.previous = std::mem::replace(&mut self.current, next);
+        self.previous.clone()
     }
 
-    fn next_token(&mut self) -> Token {
-        let token = self.current.clone();
-        self.current = self.lexer.next_token();
-        token
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        if let TokenKind::Error(message) = &self.current.kind {
+            Diagnostic::error(message.clone(), self.current.span)
+        } else {
+            Diagnostic::error(format!("Expected {} got {}", expected, self.current.kind), self.current.span)
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, Diagnostic> {
+        if self.current.kind != kind {
+            return Result::Err(self.unexpected(&kind.to_string()));
+        }
+        Result::Ok(self.next_token())
+    }
+
+    /// Skips tokens until the next `;` or `}` that is not nested inside a `{ }` block,
+    /// without consuming it, so parsing can resume after a bad statement.
+    /// A whole `{ }` block that gets skipped also ends the statement (e.g. a procedure body).
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.current.kind {
+                TokenKind::EndOfFile => break,
+                TokenKind::Semicolon if depth == 0 => break,
+                TokenKind::RBrace if depth == 0 => break,
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.next_token();
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            self.next_token();
+        }
+    }
+
+    fn recover_statement(&mut self, result: Result<AstStatement, Diagnostic>, start_position: usize) -> AstStatement {
+        match result {
+            Result::Ok(statement) => statement,
+            Result::Err(diagnostic) => {
+                self.diagnostics.push(diagnostic);
+                self.synchronize();
+                if self.current.kind == TokenKind::Semicolon {
+                    self.next_token();
+                } else if self.current.span.start == start_position && self.current.kind != TokenKind::EndOfFile {
+                    // Nothing was consumed (e.g. a stray '}'), skip it so we don't loop forever
+                    self.next_token();
+                }
+                AstStatement::Error
+            }
+        }
+    }
+
+    /// The span from `start` up to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        if self.previous.span.end > start.start {
+            start.to(self.previous.span)
+        } else {
+            start
+        }
+    }
+
+    fn parse_expression_or_error(&mut self, parent_data: ParentData) -> AstExpression {
+        let start = self.current.span;
+        match self.parse_expression(parent_data) {
+            Result::Ok(expression) => expression,
+            Result::Err(diagnostic) => {
+                self.diagnostics.push(diagnostic);
+                self.synchronize();
+                AstExpression::Error(self.span_from(start))
+            }
+        }
+    }
+
+    /// Parses the whole file, returning the tree (with `Error` placeholders where parsing failed)
+    /// together with every diagnostic that was reported.
+    pub fn parse_partial(&mut self) -> (Rc<RefCell<Ast>>, Vec<Diagnostic>) {
+        let file = self.parse_file((Option::None, Option::None));
+        (Rc::new(RefCell::new(Ast::File(file))), std::mem::take(&mut self.diagnostics))
     }
 
-    pub fn parse(&mut self) -> Rc<RefCell<Ast>> {
-        Rc::new(RefCell::new(Ast::File(self.parse_file((Option::None, Option::None)))))
+    pub fn parse(&mut self) -> Result<Rc<RefCell<Ast>>, Vec<Diagnostic>> {
+        let (ast, diagnostics) = self.parse_partial();
+        if diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+            Result::Err(diagnostics)
+        } else {
+            Result::Ok(ast)
+        }
     }
 
     fn parse_file(&mut self, parent_data: ParentData) -> Rc<RefCell<AstFile>> {
         let file = Rc::new(RefCell::new(AstFile {
             parent_data: parent_data.clone(),
-            file_path: self.file_path.clone(),
-            source: self.source.clone(),
+            file_id: self.file_id,
             scope: Rc::new(RefCell::new(AstScope {
                 parent_data: (Option::None, Option::None),
                 statements: Vec::new(),
+                value: Option::None,
+                owner: Option::None,
             })),
         }));
 
@@ -50,36 +155,58 @@ impl Parser {
 
         let data = file.borrow().scope.borrow().parent_data.clone();
         while self.current.kind != TokenKind::EndOfFile {
-            file.borrow_mut().scope.borrow_mut().statements.push(Rc::new(RefCell::new(self.parse_statement(data.clone()))));
+            let start_position = self.current.span.start;
+            let statement = self.parse_statement(data.clone());
+            let statement = self.recover_statement(statement, start_position);
+            file.borrow_mut().scope.borrow_mut().statements.push(Rc::new(RefCell::new(statement)));
         }
 
         file
     }
 
-    fn parse_scope(&mut self, parent_data: ParentData) -> Rc<RefCell<AstScope>> {
-        if self.current.kind != TokenKind::LBrace {
-            panic!("Expected '{{' got {:?}", self.current);
-        }
-        self.next_token();
+    fn parse_scope(&mut self, parent_data: ParentData) -> Result<Rc<RefCell<AstScope>>, Diagnostic> {
+        self.expect(TokenKind::LBrace)?;
 
         let scope = Rc::new(RefCell::new(AstScope {
             parent_data: parent_data.clone(),
             statements: Vec::new(),
+            value: Option::None,
+            owner: Option::None,
         }));
 
-        while self.current.kind != TokenKind::RBrace {
-            scope.borrow_mut().statements.push(Rc::new(RefCell::new(self.parse_statement((parent_data.0.clone(), Option::Some(Rc::downgrade(&scope.clone())))))));
+        while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EndOfFile {
+            let start_position = self.current.span.start;
+            let statement = self.parse_statement((parent_data.0.clone(), Option::Some(Rc::downgrade(&scope.clone()))));
+            let statement = self.recover_statement(statement, start_position);
+
+            // An expression without a ';' at the end of the scope is the value of the scope
+            if self.current.kind == TokenKind::RBrace {
+                let value = match &statement {
+                    AstStatement::Expression(expression) if self.previous.kind != TokenKind::Semicolon => Option::Some(expression.clone()),
+                    AstStatement::If(if_) if if_.borrow().yields_value() => Option::Some(Rc::new(RefCell::new(AstExpression::If(if_.clone())))),
+                    AstStatement::Match(match_) if match_.borrow().yields_value() => Option::Some(Rc::new(RefCell::new(AstExpression::Match(match_.clone())))),
+                    _ => Option::None,
+                };
+
+                if value.is_some() {
+                    scope.borrow_mut().value = value;
+                    continue;
+                }
+            }
+
+            scope.borrow_mut().statements.push(Rc::new(RefCell::new(statement)));
         }
 
-        if self.current.kind != TokenKind::RBrace {
-            panic!("Expected '}}' got {:?}", self.current);
+        if let Result::Err(diagnostic) = self.expect(TokenKind::RBrace) {
+            self.diagnostics.push(diagnostic);
         }
-        self.next_token();
 
-        scope
+        Result::Ok(scope)
     }
 
-    fn parse_statement(&mut self, parent_data: ParentData) -> AstStatement {
+    fn parse_statement(&mut self, parent_data: ParentData) -> Result<AstStatement, Diagnostic> {
+        let doc_comments = std::mem::take(&mut self.doc_comments);
+
         match self.current.kind {
             TokenKind::Semicolon => {
                 self.next_token();
@@ -87,24 +214,46 @@ impl Parser {
             }
 
             TokenKind::LBrace => {
-                AstStatement::Scope(self.parse_scope(parent_data))
+                Result::Ok(AstStatement::Scope(self.parse_scope(parent_data)?))
             }
 
+            TokenKind::If => {
+                Result::Ok(AstStatement::If(self.parse_if(parent_data)?))
+            }
+
+            TokenKind::Match => {
+                Result::Ok(AstStatement::Match(self.parse_match(parent_data)?))
+            }
+
+            TokenKind::While |
+            TokenKind::Loop |
+            TokenKind::For => self.parse_loop(Option::None, parent_data),
+
+            TokenKind::Break |
+            TokenKind::Continue => self.parse_break_or_continue(parent_data),
+
+            TokenKind::Return => self.parse_return(parent_data),
+
             _ => {
-                let expression = self.parse_expression(parent_data.clone());
+                let expression = self.parse_expression(parent_data.clone())?;
 
                 match self.current.kind {
                     TokenKind::Colon => {
-                        self.next_token();
+                        let colon = self.next_token();
 
                         let name = if let AstExpression::Name(token) = expression {
                             token.borrow().token.clone()
                         } else {
-                            panic!("Expected name before ':'");
+                            return Result::Err(Diagnostic::error(String::from("Expected name before ':'"), colon.span));
                         };
 
+                        // 'name: while ...' labels a loop
+                        if matches!(self.current.kind, TokenKind::While | TokenKind::Loop | TokenKind::For) {
+                            return self.parse_loop(Option::Some(name), parent_data);
+                        }
+
                         let type_ = if self.current.kind != TokenKind::Colon && self.current.kind != TokenKind::Equals {
-                            Option::Some(self.parse_type(parent_data.clone()))
+                            Option::Some(self.parse_type(parent_data.clone())?)
                         } else {
                             Option::None
                         };
@@ -116,131 +265,267 @@ impl Parser {
                             self.next_token();
                             true
                         } else {
-                            panic!("Expected ':' or '=' got {:?}", self.current);
+                            return Result::Err(self.unexpected("':' or '='"));
                         };
 
                         let value = if self.current.kind != TokenKind::Semicolon {
-                            Option::Some(self.parse_expression(parent_data.clone()))
+                            Option::Some(self.parse_expression_or_error(parent_data.clone()))
                         } else {
                             Option::None
                         };
 
-                        if let Option::Some(expression) = &value {
-                            if !matches!(expression, AstExpression::Procedure(_)) {
-                                if self.current.kind != TokenKind::Semicolon {
-                                    panic!("Expected ';' got {:?}", self.current);
+                        match value {
+                            Option::Some(AstExpression::Procedure(_)) |
+                            Option::Some(AstExpression::Struct(_)) |
+                            Option::Some(AstExpression::Enum(_)) => {
+                                // These end with '}' so the ';' is optional
+                                if self.current.kind == TokenKind::Semicolon {
+                                    self.next_token();
                                 }
-                                self.next_token();
                             }
-                        } else {
-                            if self.current.kind != TokenKind::Semicolon {
-                                panic!("Expected ';' got {:?}", self.current);
+                            // Recovery stopped after a block rather than at a ';'
+                            Option::Some(AstExpression::Error(_)) if self.current.kind != TokenKind::Semicolon => {}
+                            _ => {
+                                self.expect(TokenKind::Semicolon)?;
                             }
-                            self.next_token();
                         }
 
-                        if matches!(value, Option::None) & &matches!(type_, Option::None) {
-                            panic!("Cannot have a declaration with nether type nor value");
+                        let type_kind = match &value {
+                            Option::Some(AstExpression::Struct(struct_)) => Option::Some(("Struct", "struct", struct_.borrow().span)),
+                            Option::Some(AstExpression::Enum(enum_)) => Option::Some(("Enum", "enum", enum_.borrow().span)),
+                            _ => Option::None,
+                        };
+                        if let (Option::Some((kind, keyword, span)), false) = (type_kind, constant) {
+                            self.diagnostics.push(
+                                Diagnostic::error(format!("{} types must be declared as constants", kind), span)
+                                    .with_help(format!("use '{} :: {} {{ ... }}'", name.identifier(), keyword))
+                            );
+                        }
+
+                        // The ';' is already consumed, so report it here rather than resynchronizing past the next statement
+                        if value.is_none() && type_.is_none() {
+                            self.diagnostics.push(
+                                Diagnostic::error(String::from("Cannot have a declaration with neither type nor value"), name.span)
+                                    .with_help(String::from("give it a value ('name := value;' or 'name: type = value;')"))
+                            );
+                            return Result::Ok(AstStatement::Error);
                         }
 
-                        AstStatement::Declaration(Rc::new(RefCell::new(
+                        Result::Ok(AstStatement::Declaration(Rc::new(RefCell::new(
                             AstDeclaration {
                                 parent_data: parent_data.clone(),
                                 name,
                                 type_: Rc::new(RefCell::new(type_)),
                                 value: Rc::new(RefCell::new(value)),
                                 constant,
+                                doc_comments,
                             }
-                        )))
+                        ))))
                     }
 
+                    TokenKind::Equals |
                     TokenKind::PlusEquals |
                     TokenKind::MinusEquals |
                     TokenKind::AsteriskEquals |
                     TokenKind::SlashEquals |
-                    TokenKind::PercentEquals => {
+                    TokenKind::PercentEquals |
+                    TokenKind::AmpersandEquals |
+                    TokenKind::PipeEquals |
+                    TokenKind::CaretEquals |
+                    TokenKind::LessThanLessThanEquals |
+                    TokenKind::GreaterThanGreaterThanEquals => {
                         let operator = self.next_token();
-                        let right = self.parse_expression(parent_data.clone());
 
-                        if self.current.kind != TokenKind::Semicolon {
-                            panic!("Expected ';' got {:?}", self.current);
+                        if !expression.is_place() && !expression.indexes_temporary() {
+                            // Keep parsing the assignment, the statement itself is still well formed
+                            self.diagnostics.push(
+                                Diagnostic::error(String::from("Invalid left-hand side of assignment"), operator.span)
+                                    .with_label(expression.span(), String::from("cannot assign to this expression"))
+                            );
+                        }
+
+                        let right = self.parse_expression_or_error(parent_data.clone());
+
+                        if !matches!(right, AstExpression::Error(_)) || self.current.kind == TokenKind::Semicolon {
+                            self.expect(TokenKind::Semicolon)?;
                         }
-                        self.next_token();
 
-                        AstStatement::Assignment(Rc::new(RefCell::new(
+                        Result::Ok(AstStatement::Assignment(Rc::new(RefCell::new(
                             AstAssignment {
                                 parent_data: parent_data.clone(),
                                 left: Rc::new(RefCell::new(expression)),
                                 operator,
                                 right: Rc::new(RefCell::new(right)),
                             }
-                        )))
+                        ))))
                     }
 
                     _ => {
-                        if self.current.kind != TokenKind::Semicolon {
-                            panic!("Expected ';' got {:?}", self.current);
+                        // The last expression of a scope may leave out the ';' to become its value
+                        if self.current.kind != TokenKind::RBrace {
+                            self.expect(TokenKind::Semicolon)?;
                         }
-                        self.next_token();
 
-                        AstStatement::Expression(Rc::new(RefCell::new(expression)))
+                        Result::Ok(AstStatement::Expression(Rc::new(RefCell::new(expression))))
                     },
                 }
             }
         }
     }
 
-    fn parse_type(&mut self, parent_data: ParentData) -> AstType {
+    fn parse_type(&mut self, parent_data: ParentData) -> Result<AstType, Diagnostic> {
+        let start = self.current.span;
         match self.current.kind {
             TokenKind::Identifier(_) => {
-                AstType::Name(Rc::new(RefCell::new(
+                Result::Ok(AstType::Name(Rc::new(RefCell::new(
                     AstName {
                         parent_data: parent_data.clone(),
-                        token: self.next_token()
+                        token: self.next_token(),
+                        declaration: Option::None,
+                    }
+                ))))
+            }
+
+            TokenKind::Caret => {
+                self.next_token();
+                let pointee = self.parse_type(parent_data.clone())?;
+                Result::Ok(AstType::Pointer(Rc::new(RefCell::new(
+                    AstPointerType {
+                        parent_data: parent_data.clone(),
+                        span: self.span_from(start),
+                        pointee: Rc::new(RefCell::new(pointee)),
+                    }
+                ))))
+            }
+
+            TokenKind::QuestionMark => {
+                self.next_token();
+                let inner = self.parse_type(parent_data.clone())?;
+                Result::Ok(AstType::Optional(Rc::new(RefCell::new(
+                    AstOptionalType {
+                        parent_data: parent_data.clone(),
+                        span: self.span_from(start),
+                        inner: Rc::new(RefCell::new(inner)),
                     }
-                )))
+                ))))
             }
 
-            _ => panic!("Unexpected {:?}", self.current),
+            TokenKind::LBracket => {
+                self.next_token();
+
+                if self.current.kind == TokenKind::RBracket {
+                    self.next_token();
+                    let element = self.parse_type(parent_data.clone())?;
+                    return Result::Ok(AstType::Slice(Rc::new(RefCell::new(
+                        AstSliceType {
+                            parent_data: parent_data.clone(),
+                            span: self.span_from(start),
+                            element: Rc::new(RefCell::new(element)),
+                        }
+                    ))));
+                }
+
+                let length = self.parse_expression(parent_data.clone())?;
+                self.expect(TokenKind::RBracket)?;
+                let element = self.parse_type(parent_data.clone())?;
+                Result::Ok(AstType::Array(Rc::new(RefCell::new(
+                    AstArrayType {
+                        parent_data: parent_data.clone(),
+                        span: self.span_from(start),
+                        length: Rc::new(RefCell::new(length)),
+                        element: Rc::new(RefCell::new(element)),
+                    }
+                ))))
+            }
+
+            TokenKind::LParen => {
+                self.next_token();
+
+                let mut parameters = Vec::new();
+                while self.current.kind != TokenKind::RParen {
+                    parameters.push(Rc::new(RefCell::new(self.parse_type(parent_data.clone())?)));
+
+                    if self.current.kind != TokenKind::Comma {
+                        break;
+                    }
+                    self.next_token();
+                }
+                self.expect(TokenKind::RParen)?;
+
+                let return_type = if self.current.kind == TokenKind::RightArrow {
+                    self.next_token();
+                    Option::Some(self.parse_type(parent_data.clone())?)
+                } else {
+                    Option::None
+                };
+
+                Result::Ok(AstType::Procedure(Rc::new(RefCell::new(
+                    AstProcedureType {
+                        parent_data: parent_data.clone(),
+                        span: self.span_from(start),
+                        parameters,
+                        return_type: Rc::new(RefCell::new(return_type)),
+                    }
+                ))))
+            }
+
+            _ => Result::Err(self.unexpected("type")),
         }
     }
 
-    fn parse_expression(&mut self, parent_data: ParentData) -> AstExpression {
+    fn parse_expression(&mut self, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
         self.parse_binary_expression(0, parent_data)
     }
 
-    fn parse_procedure(&mut self, first_arg_name: Option<AstName>, parent_data: ParentData) -> AstExpression {
-        let arguments = if first_arg_name.is_none() {
-            Vec::new()
+    fn with_struct_literals<T>(&mut self, allowed: bool, parse: impl FnOnce(&mut Parser) -> T) -> T {
+        let outer = std::mem::replace(&mut self.struct_literals, allowed);
+        let result = parse(self);
+        self.struct_literals = outer;
+        result
+    }
+
+    /// Parses an expression that is followed by a `{ }` block
+    fn parse_condition(&mut self, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
+        self.with_struct_literals(false, |parser| parser.parse_expression(parent_data))
+    }
+
+    /// Parses the rest of a procedure parameter after its name and ':', i.e. 'type', 'type = value' or '= value'
+    fn parse_parameter(&mut self, name: Token, parent_data: ParentData) -> Result<Rc<RefCell<AstDeclaration>>, Diagnostic> {
+        let type_ = if self.current.kind != TokenKind::Equals {
+            Option::Some(self.parse_type(parent_data.clone())?)
         } else {
-            let mut args = Vec::new();
+            Option::None
+        };
 
-            let first_arg_type = if self.current.kind != TokenKind::Equals {
-                Option::Some(self.parse_type(parent_data.clone()))
-            } else {
-                Option::None
-            };
+        let value = if self.current.kind == TokenKind::Equals {
+            self.next_token();
+            Option::Some(self.parse_expression(parent_data.clone())?)
+        } else if self.current.kind != TokenKind::Comma && self.current.kind != TokenKind::RParen {
+            return Result::Err(self.unexpected("'=', ',' or ')'"));
+        } else {
+            Option::None
+        };
 
-            let first_arg_value = if self.current.kind != TokenKind::Comma && self.current.kind != TokenKind::RParen {
-                if self.current.kind != TokenKind::Equals {
-                    panic!("Expected '=' got {:?}", self.current);
-                }
-                Option::Some(self.parse_expression(parent_data.clone()))
-            } else {
-                Option::None
-            };
+        if value.is_none() && type_.is_none() {
+            return Result::Err(Diagnostic::error(
+                String::from("Cannot have a procedure argument with neither type nor value"),
+                name.span,
+            ));
+        }
 
-            if matches!(first_arg_value, Option::None) && matches!(first_arg_type, Option::None) {
-                panic!("Cannot have a procedure argument with nether type nor value");
-            }
+        Result::Ok(Rc::new(RefCell::new(AstDeclaration {
+            parent_data: parent_data.clone(),
+            name,
+            type_: Rc::new(RefCell::new(type_)),
+            value: Rc::new(RefCell::new(value)),
+            constant: false,
+            doc_comments: Vec::new(),
+        })))
+    }
 
-            args.push(Rc::new(RefCell::new(AstDeclaration {
-                parent_data: parent_data.clone(),
-                name: first_arg_name.unwrap().token,
-                type_: Rc::new(RefCell::new(first_arg_type)),
-                value: Rc::new(RefCell::new(first_arg_value)),
-                constant: false,
-            })));
+    fn parse_procedure(&mut self, start: Span, first_arg_name: Option<AstName>, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
+        let arguments = if let Option::Some(first_arg_name) = first_arg_name {
+            let mut args = vec![self.parse_parameter(first_arg_name.token, parent_data.clone())?];
 
             while self.current.kind != TokenKind::RParen {
                 if self.current.kind != TokenKind::Comma {
@@ -248,118 +533,826 @@ impl Parser {
                 }
                 self.next_token();
 
-                let name = self.next_token();
-                if !matches!(name.kind, TokenKind::Identifier(_)) {
-                    panic!("Expected name got {:?}", name);
-                }
-
-                if self.current.kind != TokenKind::Colon {
-                    panic!("Expected ':' got {:?}", self.current);
+                if !matches!(self.current.kind, TokenKind::Identifier(_)) {
+                    return Result::Err(self.unexpected("name"));
                 }
-                self.next_token();
-
-                let type_ = if self.current.kind != TokenKind::Equals {
-                    Option::Some(self.parse_type(parent_data.clone()))
-                } else {
-                    Option::None
-                };
-
-                let value = if self.current.kind != TokenKind::Comma {
-                    if self.current.kind != TokenKind::Equals {
-                        panic!("Expected '=' got {:?}", self.current);
-                    }
-                    Option::Some(self.parse_expression(parent_data.clone()))
-                } else {
-                    Option::None
-                };
+                let name = self.next_token();
 
-                if matches!(value, Option::None) && matches!(type_, Option::None) {
-                    panic!("Cannot have a procedure argument with nether type nor value");
-                }
+                self.expect(TokenKind::Colon)?;
 
-                args.push(Rc::new(RefCell::new(AstDeclaration {
-                    parent_data: parent_data.clone(),
-                    name,
-                    type_: Rc::new(RefCell::new(type_)),
-                    value: Rc::new(RefCell::new(value)),
-                    constant: false,
-                })));
+                args.push(self.parse_parameter(name, parent_data.clone())?);
             }
 
-            if self.current.kind != TokenKind::RParen {
-                panic!("Expected ')' got {:?}", self.current);
-            } else {
-                self.next_token();
-            }
+            self.expect(TokenKind::RParen)?;
 
             args
+        } else {
+            Vec::new()
         };
 
         let return_type = if self.current.kind == TokenKind::RightArrow {
             self.next_token();
-            Option::Some(self.parse_type(parent_data.clone()))
+            Option::Some(self.parse_type(parent_data.clone())?)
         } else {
             Option::None
         };
 
+        // 'break' and 'continue' can't reach loops outside of the procedure
+        let loop_labels = std::mem::take(&mut self.loop_labels);
+        let outer_return_type = self.return_type.replace(return_type.clone());
         let scope = self.parse_scope(parent_data.clone());
+        self.loop_labels = loop_labels;
+        self.return_type = outer_return_type;
+        let scope = scope?;
 
-        AstExpression::Procedure(Rc::new(RefCell::new(
+        if let Option::Some(return_type) = &return_type {
+            if !return_type.is_void() && scope.borrow().value.is_none() && !scope.borrow().diverges() {
+                self.diagnostics.push(
+                    Diagnostic::error(String::from("Not every path through the procedure returns a value"), self.previous.span)
+                        .with_label(return_type.span(), String::from("the procedure returns a value of this type"))
+                        .with_help(String::from("end the procedure with a 'return' or a final expression without a ';'"))
+                );
+            }
+        }
+
+        let procedure = Rc::new(RefCell::new(
             AstProcedure {
                 parent_data: parent_data.clone(),
+                span: self.span_from(start),
                 arguments,
                 return_type: Rc::new(RefCell::new(return_type)),
-                scope,
+                scope: scope.clone(),
+            }
+        ));
+        scope.borrow_mut().owner = Option::Some(AstScopeOwner::Procedure(Rc::downgrade(&procedure)));
+
+        Result::Ok(AstExpression::Procedure(procedure))
+    }
+
+    fn parse_loop_body(&mut self, label: &Option<Token>, parent_data: ParentData) -> Result<Rc<RefCell<AstScope>>, Diagnostic> {
+        self.loop_labels.push(label.clone());
+        let body = self.parse_scope(parent_data);
+        self.loop_labels.pop();
+        body
+    }
+
+    fn parse_loop(&mut self, label: Option<Token>, parent_data: ParentData) -> Result<AstStatement, Diagnostic> {
+        let start = label.as_ref().map_or(self.current.span, |label| label.span);
+
+        match self.current.kind {
+            TokenKind::While => {
+                self.next_token();
+                let condition = self.parse_condition(parent_data.clone())?;
+                let body = self.parse_loop_body(&label, parent_data.clone())?;
+
+                let while_ = Rc::new(RefCell::new(AstWhile {
+                    parent_data: parent_data.clone(),
+                    span: self.span_from(start),
+                    label,
+                    condition: Rc::new(RefCell::new(condition)),
+                    body: body.clone(),
+                }));
+                body.borrow_mut().owner = Option::Some(AstScopeOwner::While(Rc::downgrade(&while_)));
+
+                Result::Ok(AstStatement::While(while_))
+            }
+
+            TokenKind::Loop => {
+                self.next_token();
+                let body = self.parse_loop_body(&label, parent_data.clone())?;
+
+                let loop_ = Rc::new(RefCell::new(AstLoop {
+                    parent_data: parent_data.clone(),
+                    span: self.span_from(start),
+                    label,
+                    body: body.clone(),
+                }));
+                body.borrow_mut().owner = Option::Some(AstScopeOwner::Loop(Rc::downgrade(&loop_)));
+
+                Result::Ok(AstStatement::Loop(loop_))
+            }
+
+            TokenKind::For => {
+                self.next_token();
+
+                if !matches!(self.current.kind, TokenKind::Identifier(_)) {
+                    return Result::Err(self.unexpected("name"));
+                }
+                let name = self.next_token();
+
+                self.expect(TokenKind::In)?;
+                let range_start = self.parse_condition(parent_data.clone())?;
+                self.expect(TokenKind::DotDot)?;
+                let range_end = self.parse_condition(parent_data.clone())?;
+
+                let body = self.parse_loop_body(&label, parent_data.clone())?;
+
+                let variable = Rc::new(RefCell::new(AstDeclaration {
+                    parent_data: (parent_data.0.clone(), Option::Some(Rc::downgrade(&body))),
+                    name,
+                    type_: Rc::new(RefCell::new(Option::None)),
+                    value: Rc::new(RefCell::new(Option::None)),
+                    constant: false,
+                    doc_comments: Vec::new(),
+                }));
+
+                let for_ = Rc::new(RefCell::new(AstFor {
+                    parent_data: parent_data.clone(),
+                    span: self.span_from(start),
+                    label,
+                    variable,
+                    start: Rc::new(RefCell::new(range_start)),
+                    end: Rc::new(RefCell::new(range_end)),
+                    body: body.clone(),
+                }));
+                body.borrow_mut().owner = Option::Some(AstScopeOwner::For(Rc::downgrade(&for_)));
+
+                Result::Ok(AstStatement::For(for_))
             }
-        )))
+
+            _ => Result::Err(self.unexpected("'while', 'loop' or 'for'")),
+        }
     }
 
-    fn parse_primary_expression(&mut self, parent_data: ParentData) -> AstExpression {
+    fn parse_return(&mut self, parent_data: ParentData) -> Result<AstStatement, Diagnostic> {
+        let keyword = self.next_token();
+
+        let value = if self.current.kind != TokenKind::Semicolon && self.current.kind != TokenKind::RBrace {
+            Option::Some(Rc::new(RefCell::new(self.parse_expression_or_error(parent_data.clone()))))
+        } else {
+            Option::None
+        };
+        let span = self.span_from(keyword.span);
+
+        if self.current.kind != TokenKind::RBrace {
+            self.expect(TokenKind::Semicolon)?;
+        }
+
+        match &self.return_type {
+            Option::None => {
+                self.diagnostics.push(Diagnostic::error(String::from("'return' outside of a procedure"), span));
+            }
+            Option::Some(Option::Some(return_type)) if !return_type.is_void() => {
+                if value.is_none() {
+                    self.diagnostics.push(
+                        Diagnostic::error(String::from("Expected a value to return"), span)
+                            .with_label(return_type.span(), String::from("the procedure returns a value of this type"))
+                    );
+                }
+            }
+            Option::Some(_) => {
+                if value.is_some() {
+                    self.diagnostics.push(Diagnostic::error(String::from("Cannot return a value from a procedure without a return type"), span));
+                }
+            }
+        }
+
+        Result::Ok(AstStatement::Return(Rc::new(RefCell::new(AstReturn {
+            parent_data,
+            span,
+            value,
+        }))))
+    }
+
+    fn parse_break_or_continue(&mut self, parent_data: ParentData) -> Result<AstStatement, Diagnostic> {
+        let keyword = self.next_token();
+
+        let label = if matches!(self.current.kind, TokenKind::Identifier(_)) {
+            Option::Some(self.next_token())
+        } else {
+            Option::None
+        };
+        let span = self.span_from(keyword.span);
+
+        if self.current.kind != TokenKind::RBrace {
+            self.expect(TokenKind::Semicolon)?;
+        }
+
+        if let Option::Some(label) = &label {
+            if !self.loop_labels.iter().flatten().any(|loop_label| loop_label.kind == label.kind) {
+                self.diagnostics.push(Diagnostic::error(format!("Unknown loop label '{}'", label.identifier()), label.span));
+            }
+        } else if self.loop_labels.is_empty() {
+            self.diagnostics.push(Diagnostic::error(format!("{} outside of a loop", keyword.kind), span));
+        }
+
+        if keyword.kind == TokenKind::Break {
+            Result::Ok(AstStatement::Break(Rc::new(RefCell::new(AstBreak {
+                parent_data,
+                span,
+                label,
+            }))))
+        } else {
+            Result::Ok(AstStatement::Continue(Rc::new(RefCell::new(AstContinue {
+                parent_data,
+                span,
+                label,
+            }))))
+        }
+    }
+
+    fn parse_if(&mut self, parent_data: ParentData) -> Result<Rc<RefCell<AstIf>>, Diagnostic> {
+        let start = self.expect(TokenKind::If)?.span;
+
+        let condition = self.parse_condition(parent_data.clone())?;
+        let then_scope = self.parse_scope(parent_data.clone())?;
+
+        let else_ = if self.current.kind == TokenKind::Else {
+            self.next_token();
+            if self.current.kind == TokenKind::If {
+                Option::Some(AstElse::If(self.parse_if(parent_data.clone())?))
+            } else {
+                Option::Some(AstElse::Scope(self.parse_scope(parent_data.clone())?))
+            }
+        } else {
+            Option::None
+        };
+
+        Result::Ok(Rc::new(RefCell::new(AstIf {
+            parent_data: parent_data.clone(),
+            span: self.span_from(start),
+            condition: Rc::new(RefCell::new(condition)),
+            then_scope,
+            else_,
+        })))
+    }
+
+    fn parse_primary_expression(&mut self, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
         match self.current.kind {
-            TokenKind::Identifier(_) => AstExpression::Name(Rc::new(RefCell::new(
+            TokenKind::Identifier(_) => Result::Ok(AstExpression::Name(Rc::new(RefCell::new(
                 AstName {
                     parent_data: parent_data.clone(),
-                    token: self.next_token()
+                    token: self.next_token(),
+                    declaration: Option::None,
                 }
-            ))),
+            )))),
 
-            TokenKind::Integer(_) |
-            TokenKind::Float(_) => AstExpression::Literal(Rc::new(RefCell::new(
+            TokenKind::Integer(..) |
+            TokenKind::Float(..) |
+            TokenKind::String(_) |
+            TokenKind::Char(_) => Result::Ok(AstExpression::Literal(Rc::new(RefCell::new(
                 AstLiteral {
                     parent_data: parent_data.clone(),
                     token: self.next_token()
                 }
-            ))),
+            )))),
 
             TokenKind::LParen => {
-                self.next_token();
+                let start = self.next_token().span;
                 if self.current.kind == TokenKind::RParen {
                     self.next_token();
-                    return self.parse_procedure(Option::None, parent_data.clone());
+                    return self.parse_procedure(start, Option::None, parent_data.clone());
                 }
-                let expression = self.parse_expression(parent_data.clone());
+                let expression = self.with_struct_literals(true, |parser| parser.parse_expression(parent_data.clone()))?;
                 if self.current.kind == TokenKind::Colon {
                     if let AstExpression::Name(name) = expression {
                         self.next_token();
-                        return self.parse_procedure(Option::Some((*name.borrow()).clone()), parent_data.clone());
-                    } else  {
-                        panic!("Expected name");
+                        return self.parse_procedure(start, Option::Some((*name.borrow()).clone()), parent_data.clone());
+                    } else {
+                        return Result::Err(Diagnostic::error(String::from("Expected name before ':'"), self.current.span));
                     }
-                } else if self.current.kind != TokenKind::RParen {
-                    panic!("Expected '(' got {:?}", self.current);
                 }
+                self.expect(TokenKind::RParen)?;
+                Result::Ok(expression)
+            }
+
+            TokenKind::LBracket => {
+                let start = self.next_token().span;
+
+                let mut elements = Vec::new();
+                while self.current.kind != TokenKind::RBracket {
+                    let element = self.with_struct_literals(true, |parser| parser.parse_expression(parent_data.clone()))?;
+                    elements.push(Rc::new(RefCell::new(element)));
+
+                    if self.current.kind != TokenKind::Comma {
+                        break;
+                    }
+                    self.next_token();
+                }
+                self.expect(TokenKind::RBracket)?;
+
+                Result::Ok(AstExpression::ArrayLiteral(Rc::new(RefCell::new(
+                    AstArrayLiteral {
+                        parent_data: parent_data.clone(),
+                        span: self.span_from(start),
+                        elements,
+                    }
+                ))))
+            }
+
+            TokenKind::Struct => self.parse_struct(parent_data.clone()),
+
+            TokenKind::Enum => self.parse_enum(parent_data.clone()),
+
+            TokenKind::Match => {
+                let match_ = self.parse_match(parent_data.clone())?;
+                if !match_.borrow().yields_value() {
+                    self.diagnostics.push(
+                        Diagnostic::error(String::from("'match' used as a value must yield a value in every arm"), match_.borrow().span)
+                    );
+                }
+                Result::Ok(AstExpression::Match(match_))
+            }
+
+            TokenKind::If => {
+                let if_ = self.parse_if(parent_data.clone())?;
+                if !if_.borrow().yields_value() {
+                    self.diagnostics.push(
+                        Diagnostic::error(String::from("'if' used as a value must yield a value in every branch"), if_.borrow().span)
+                            .with_help(String::from("add an 'else' branch and end each branch with an expression without ';'"))
+                    );
+                }
+                Result::Ok(AstExpression::If(if_))
+            }
+
+            _ => Result::Err(self.unexpected("expression")),
+        }
+    }
+
+    fn parse_call(&mut self, callee: AstExpression, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
+        self.expect(TokenKind::LParen)?;
+
+        let mut arguments: Vec<AstArgument> = Vec::new();
+        while self.current.kind != TokenKind::RParen {
+            let value = self.parse_expression(parent_data.clone())?;
+
+            let argument = if self.current.kind == TokenKind::Equals {
+                let name = if let AstExpression::Name(name) = &value {
+                    name.borrow().token.clone()
+                } else {
+                    return Result::Err(Diagnostic::error(String::from("Expected argument name before '='"), value.span()));
+                };
+                self.next_token();
+
+                if let Option::Some(previous) = arguments.iter().filter_map(|argument| argument.name.as_ref()).find(|previous| previous.kind == name.kind) {
+                    self.diagnostics.push(
+                        Diagnostic::error(format!("Argument '{}' is given more than once", name.identifier()), name.span)
+                            .with_label(previous.span, String::from("first given here"))
+                    );
+                }
+
+                AstArgument {
+                    name: Option::Some(name),
+                    value: Rc::new(RefCell::new(self.parse_expression(parent_data.clone())?)),
+                }
+            } else {
+                if let Option::Some(named) = arguments.iter().find(|argument| argument.name.is_some()) {
+                    self.diagnostics.push(
+                        Diagnostic::error(String::from("Positional arguments cannot follow named arguments"), value.span())
+                            .with_label(named.span(), String::from("named argument given here"))
+                    );
+                }
+
+                AstArgument {
+                    name: Option::None,
+                    value: Rc::new(RefCell::new(value)),
+                }
+            };
+            arguments.push(argument);
+
+            if self.current.kind != TokenKind::Comma {
+                break;
+            }
+            self.next_token();
+        }
+
+        self.expect(TokenKind::RParen)?;
+
+        Result::Ok(AstExpression::Call(Rc::new(RefCell::new(
+            AstCall {
+                parent_data: parent_data.clone(),
+                span: self.span_from(callee.span()),
+                callee: Rc::new(RefCell::new(callee)),
+                arguments,
+            }
+        ))))
+    }
+
+    fn parse_postfix_expression(&mut self, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
+        let mut expression = self.parse_primary_expression(parent_data.clone())?;
+
+        // Procedure and struct literals end with '}' like a statement, so a '(' after them starts the next statement
+        if matches!(expression, AstExpression::Procedure(_) | AstExpression::Struct(_) | AstExpression::Enum(_)) {
+            return Result::Ok(expression);
+        }
+
+        if self.current.kind == TokenKind::LBrace && self.struct_literals {
+            if let AstExpression::Name(name) = expression {
+                expression = self.parse_struct_literal(AstType::Name(name), parent_data.clone())?;
+            }
+        }
+
+        loop {
+            let postfix_caret = self.current.kind == TokenKind::Caret && self.is_postfix_caret(&expression);
+            match self.current.kind {
+                TokenKind::LParen => {
+                    expression = self.with_struct_literals(true, |parser| parser.parse_call(expression, parent_data.clone()))?;
+                }
+
+                TokenKind::LBracket => {
+                    expression = self.with_struct_literals(true, |parser| parser.parse_index(expression, parent_data.clone()))?;
+                }
+
+                TokenKind::Caret if postfix_caret => {
+                    let operator = self.next_token();
+                    expression = AstExpression::Unary(Rc::new(RefCell::new(
+                        AstUnary {
+                            parent_data: parent_data.clone(),
+                            operator,
+                            operand: Rc::new(RefCell::new(expression)),
+                        }
+                    )));
+                }
+
+                TokenKind::Dot => {
+                    self.next_token();
+                    if !matches!(self.current.kind, TokenKind::Identifier(_)) {
+                        return Result::Err(self.unexpected("field name"));
+                    }
+                    let name = self.next_token();
+
+                    expression = AstExpression::Field(Rc::new(RefCell::new(
+                        AstField {
+                            parent_data: parent_data.clone(),
+                            span: self.span_from(expression.span()),
+                            operand: Rc::new(RefCell::new(expression)),
+                            name,
+                        }
+                    )));
+                }
+
+                _ => break,
+            }
+        }
+
+        Result::Ok(expression)
+    }
+
+    /// Parses `[index]` or `[start..end]` after `operand`
+    fn parse_index(&mut self, operand: AstExpression, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
+        self.expect(TokenKind::LBracket)?;
+
+        let start = if self.current.kind != TokenKind::DotDot {
+            let index = self.parse_expression(parent_data.clone())?;
+
+            if self.current.kind != TokenKind::DotDot {
+                self.expect(TokenKind::RBracket)?;
+                return Result::Ok(AstExpression::Index(Rc::new(RefCell::new(
+                    AstIndex {
+                        parent_data: parent_data.clone(),
+                        span: self.span_from(operand.span()),
+                        operand: Rc::new(RefCell::new(operand)),
+                        index: Rc::new(RefCell::new(index)),
+                    }
+                ))));
+            }
+
+            Option::Some(Rc::new(RefCell::new(index)))
+        } else {
+            Option::None
+        };
+
+        self.expect(TokenKind::DotDot)?;
+        let end = if self.current.kind != TokenKind::RBracket {
+            Option::Some(Rc::new(RefCell::new(self.parse_expression(parent_data.clone())?)))
+        } else {
+            Option::None
+        };
+        self.expect(TokenKind::RBracket)?;
+
+        Result::Ok(AstExpression::Slice(Rc::new(RefCell::new(
+            AstSlice {
+                parent_data: parent_data.clone(),
+                span: self.span_from(operand.span()),
+                operand: Rc::new(RefCell::new(operand)),
+                start,
+                end,
+            }
+        ))))
+    }
+
+    fn parse_struct(&mut self, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
+        let start = self.expect(TokenKind::Struct)?.span;
+        self.expect(TokenKind::LBrace)?;
+
+        let mut fields: Vec<Rc<RefCell<AstDeclaration>>> = Vec::new();
+        while self.current.kind != TokenKind::RBrace {
+            let doc_comments = std::mem::take(&mut self.doc_comments);
+
+            if !matches!(self.current.kind, TokenKind::Identifier(_)) {
+                return Result::Err(self.unexpected("field name"));
+            }
+            let name = self.next_token();
+            self.expect(TokenKind::Colon)?;
+            let type_ = self.parse_type(parent_data.clone())?;
+
+            let value = if self.current.kind == TokenKind::Equals {
                 self.next_token();
-                expression
+                Option::Some(self.parse_expression(parent_data.clone())?)
+            } else {
+                Option::None
+            };
+
+            if let Option::Some(previous) = fields.iter().find(|field| field.borrow().name.kind == name.kind) {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("Field '{}' is declared more than once", name.identifier()), name.span)
+                        .with_label(previous.borrow().name.span, String::from("first declared here"))
+                );
+            }
+
+            fields.push(Rc::new(RefCell::new(AstDeclaration {
+                parent_data: parent_data.clone(),
+                name,
+                type_: Rc::new(RefCell::new(Option::Some(type_))),
+                value: Rc::new(RefCell::new(value)),
+                constant: false,
+                doc_comments,
+            })));
+
+            if self.current.kind != TokenKind::Comma {
+                break;
+            }
+            self.next_token();
+        }
+
+        self.expect(TokenKind::RBrace)?;
+
+        Result::Ok(AstExpression::Struct(Rc::new(RefCell::new(
+            AstStruct {
+                parent_data: parent_data.clone(),
+                span: self.span_from(start),
+                fields,
+            }
+        ))))
+    }
+
+    fn parse_enum(&mut self, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
+        let start = self.expect(TokenKind::Enum)?.span;
+        self.expect(TokenKind::LBrace)?;
+
+        let mut variants: Vec<AstVariant> = Vec::new();
+        while self.current.kind != TokenKind::RBrace {
+            let doc_comments = std::mem::take(&mut self.doc_comments);
+
+            if !matches!(self.current.kind, TokenKind::Identifier(_)) {
+                return Result::Err(self.unexpected("variant name"));
+            }
+            let name = self.next_token();
+
+            let mut payload = Vec::new();
+            if self.current.kind == TokenKind::LParen {
+                self.next_token();
+                while self.current.kind != TokenKind::RParen {
+                    payload.push(Rc::new(RefCell::new(self.parse_type(parent_data.clone())?)));
+
+                    if self.current.kind != TokenKind::Comma {
+                        break;
+                    }
+                    self.next_token();
+                }
+                self.expect(TokenKind::RParen)?;
+            }
+
+            if let Option::Some(previous) = variants.iter().find(|variant| variant.name.kind == name.kind) {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("Variant '{}' is declared more than once", name.identifier()), name.span)
+                        .with_label(previous.name.span, String::from("first declared here"))
+                );
+            }
+
+            variants.push(AstVariant {
+                name,
+                payload,
+                doc_comments,
+            });
+
+            if self.current.kind != TokenKind::Comma {
+                break;
+            }
+            self.next_token();
+        }
+
+        self.expect(TokenKind::RBrace)?;
+
+        Result::Ok(AstExpression::Enum(Rc::new(RefCell::new(
+            AstEnum {
+                parent_data: parent_data.clone(),
+                span: self.span_from(start),
+                variants,
+            }
+        ))))
+    }
+
+    fn parse_pattern(&mut self, parent_data: ParentData) -> Result<AstPattern, Diagnostic> {
+        match &self.current.kind {
+            TokenKind::Identifier(name) if name == "_" => Result::Ok(AstPattern::Wildcard(self.next_token())),
+
+            TokenKind::Identifier(_) => {
+                let name = self.next_token();
+
+                let mut bindings = Vec::new();
+                if self.current.kind == TokenKind::LParen {
+                    self.next_token();
+                    while self.current.kind != TokenKind::RParen {
+                        if !matches!(self.current.kind, TokenKind::Identifier(_)) {
+                            return Result::Err(self.unexpected("name"));
+                        }
+                        let binding = self.next_token();
+
+                        // The declarations are filled in once the arm's scope exists
+                        bindings.push(if binding.identifier() == "_" {
+                            Option::None
+                        } else {
+                            Option::Some(Rc::new(RefCell::new(AstDeclaration {
+                                parent_data: (Option::None, Option::None),
+                                name: binding,
+                                type_: Rc::new(RefCell::new(Option::None)),
+                                value: Rc::new(RefCell::new(Option::None)),
+                                constant: false,
+                                doc_comments: Vec::new(),
+                            })))
+                        });
+
+                        if self.current.kind != TokenKind::Comma {
+                            break;
+                        }
+                        self.next_token();
+                    }
+                    self.expect(TokenKind::RParen)?;
+                }
+
+                Result::Ok(AstPattern::Variant(Rc::new(RefCell::new(AstVariantPattern {
+                    span: self.span_from(name.span),
+                    name,
+                    bindings,
+                }))))
+            }
+
+            TokenKind::Integer(..) |
+            TokenKind::Float(..) |
+            TokenKind::String(_) |
+            TokenKind::Char(_) => Result::Ok(AstPattern::Literal(Rc::new(RefCell::new(AstLiteral {
+                parent_data,
+                token: self.next_token(),
+            })))),
+
+            _ => Result::Err(self.unexpected("pattern")),
+        }
+    }
+
+    fn parse_match(&mut self, parent_data: ParentData) -> Result<Rc<RefCell<AstMatch>>, Diagnostic> {
+        let start = self.expect(TokenKind::Match)?.span;
+        let value = self.parse_condition(parent_data.clone())?;
+        self.expect(TokenKind::LBrace)?;
+
+        let mut arms: Vec<AstMatchArm> = Vec::new();
+        while self.current.kind != TokenKind::RBrace {
+            let pattern = self.parse_pattern(parent_data.clone())?;
+            self.expect(TokenKind::FatArrow)?;
+
+            let body = if self.current.kind == TokenKind::LBrace {
+                let body = self.parse_scope(parent_data.clone())?;
+                if self.current.kind == TokenKind::Comma {
+                    self.next_token();
+                }
+                body
+            } else {
+                let body = Rc::new(RefCell::new(AstScope {
+                    parent_data: parent_data.clone(),
+                    statements: Vec::new(),
+                    value: Option::None,
+                    owner: Option::None,
+                }));
+                let value = self.with_struct_literals(true, |parser| {
+                    parser.parse_expression((parent_data.0.clone(), Option::Some(Rc::downgrade(&body))))
+                })?;
+                body.borrow_mut().value = Option::Some(Rc::new(RefCell::new(value)));
+
+                if self.current.kind != TokenKind::RBrace {
+                    self.expect(TokenKind::Comma)?;
+                }
+                body
+            };
+
+            if let AstPattern::Variant(variant) = &pattern {
+                for binding in variant.borrow().bindings.iter().flatten() {
+                    let pattern_data = (parent_data.0.clone(), Option::Some(Rc::downgrade(&body)));
+                    binding.borrow_mut().parent_data = pattern_data;
+                }
+            }
+
+            let unreachable = arms.iter().find(|arm| match (&arm.pattern, &pattern) {
+                (AstPattern::Wildcard(_), _) => true,
+                (AstPattern::Variant(previous), AstPattern::Variant(variant)) => previous.borrow().name.kind == variant.borrow().name.kind,
+                (AstPattern::Literal(previous), AstPattern::Literal(literal)) => previous.borrow().token.kind == literal.borrow().token.kind,
+                _ => false,
+            });
+            if let Option::Some(previous) = unreachable {
+                self.diagnostics.push(
+                    Diagnostic::warning(String::from("Unreachable match arm"), pattern.span())
+                        .with_label(previous.pattern.span(), String::from("already matched here"))
+                );
+            }
+
+            arms.push(AstMatchArm {
+                pattern,
+                body,
+            });
+        }
+
+        self.expect(TokenKind::RBrace)?;
+
+        Result::Ok(Rc::new(RefCell::new(AstMatch {
+            parent_data: parent_data.clone(),
+            span: self.span_from(start),
+            value: Rc::new(RefCell::new(value)),
+            arms,
+        })))
+    }
+
+    fn parse_struct_literal(&mut self, type_: AstType, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
+        let start = type_.span();
+        self.expect(TokenKind::LBrace)?;
+
+        let mut fields: Vec<AstFieldValue> = Vec::new();
+        while self.current.kind != TokenKind::RBrace {
+            if !matches!(self.current.kind, TokenKind::Identifier(_)) {
+                return Result::Err(self.unexpected("field name"));
+            }
+            let name = self.next_token();
+            self.expect(TokenKind::Equals)?;
+            let value = self.with_struct_literals(true, |parser| parser.parse_expression(parent_data.clone()))?;
+
+            if let Option::Some(previous) = fields.iter().find(|field| field.name.kind == name.kind) {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("Field '{}' is given more than once", name.identifier()), name.span)
+                        .with_label(previous.name.span, String::from("first given here"))
+                );
+            }
+
+            fields.push(AstFieldValue {
+                name,
+                value: Rc::new(RefCell::new(value)),
+            });
+
+            if self.current.kind != TokenKind::Comma {
+                break;
+            }
+            self.next_token();
+        }
+
+        self.expect(TokenKind::RBrace)?;
+
+        Result::Ok(AstExpression::StructLiteral(Rc::new(RefCell::new(
+            AstStructLiteral {
+                parent_data: parent_data.clone(),
+                span: self.span_from(start),
+                type_: Rc::new(RefCell::new(type_)),
+                fields,
             }
+        ))))
+    }
 
-            _ => panic!("Unexpected token {:?}", self.current),
+    /// Whether the current '^' after `operand` is a postfix dereference rather than xor.
+    /// It has to touch the operand and be followed by whitespace or something that can't start an operand,
+    /// so 'p^ + 1', 'p^.x' and 'p^ ^ 2' dereference, while 'a ^ b', 'a ^ -b' and 'a^b' are xor
+    fn is_postfix_caret(&mut self, operand: &AstExpression) -> bool {
+        if operand.span().end != self.current.span.start {
+            return false;
         }
+
+        let next = self.lexer.peek_token();
+        next.span.start != self.current.span.end || !Parser::starts_operand(&next) ||
+            // 'p^^' dereferences twice
+            next.kind == TokenKind::Caret
+    }
+
+    /// Whether the token can begin the operand of a binary operator,
+    /// '[' is left out so that 'p^[i]' indexes the dereferenced pointer
+    fn starts_operand(token: &Token) -> bool {
+        matches!(
+            token.kind,
+            TokenKind::Identifier(_) |
+            TokenKind::Integer(..) |
+            TokenKind::Float(..) |
+            TokenKind::String(_) |
+            TokenKind::Char(_) |
+            TokenKind::LParen |
+            TokenKind::If |
+            TokenKind::Match |
+            TokenKind::Struct |
+            TokenKind::Enum
+        ) || Parser::unary_operator_precedence(token) != 0
     }
 
     fn unary_operator_precedence(token: &Token) -> u64 {
         match token.kind {
-            TokenKind::Plus => 3,
-            TokenKind::Minus => 3,
+            TokenKind::Plus => 11,
+            TokenKind::Minus => 11,
+            TokenKind::ExclamationMark => 11,
+            TokenKind::Tilde => 11,
+            TokenKind::Ampersand => 11,
+            TokenKind::Caret => 11,
 
             _ => 0,
         }
@@ -367,22 +1360,53 @@ impl Parser {
 
     fn binary_operator_precedence(token: &Token) -> u64 {
         match token.kind {
-            TokenKind::Asterisk => 2,
-            TokenKind::Slash => 2,
-            TokenKind::Percent => 2,
+            TokenKind::Asterisk => 10,
+            TokenKind::Slash => 10,
+            TokenKind::Percent => 10,
+
+            TokenKind::Plus => 9,
+            TokenKind::Minus => 9,
+
+            TokenKind::LessThanLessThan => 8,
+            TokenKind::GreaterThanGreaterThan => 8,
+
+            TokenKind::Ampersand => 7,
 
-            TokenKind::Plus => 1,
-            TokenKind::Minus => 1,
+            TokenKind::Caret => 6,
+
+            TokenKind::Pipe => 5,
+
+            TokenKind::LessThan => 4,
+            TokenKind::LessThanEquals => 4,
+            TokenKind::GreaterThan => 4,
+            TokenKind::GreaterThanEquals => 4,
+
+            TokenKind::EqualsEquals => 3,
+            TokenKind::ExclamationMarkEquals => 3,
+
+            TokenKind::AmpersandAmpersand => 2,
+
+            TokenKind::PipePipe => 1,
 
             _ => 0,
         }
     }
 
-    fn parse_binary_expression(&mut self, parent_precedence: u64, parent_data: ParentData) -> AstExpression {
+    fn parse_binary_expression(&mut self, parent_precedence: u64, parent_data: ParentData) -> Result<AstExpression, Diagnostic> {
         let unary_precedence = Parser::unary_operator_precedence(&self.current);
-        let mut left = if unary_precedence > parent_precedence {
+        // '>=' so that unary operators can be stacked, e.g. '!!a' or '- -a'
+        let mut left = if unary_precedence != 0 && unary_precedence >= parent_precedence {
             let operator = self.next_token();
-            let operand = self.parse_binary_expression(unary_precedence, parent_data.clone());
+            let operand = self.parse_binary_expression(unary_precedence, parent_data.clone())?;
+
+            if operator.kind == TokenKind::Ampersand && !operand.is_place() && !operand.indexes_temporary() {
+                self.diagnostics.push(
+                    Diagnostic::error(String::from("Cannot take the address of a temporary value"), operator.span)
+                        .with_label(operand.span(), String::from("this value is not stored anywhere"))
+                        .with_help(String::from("store it in a variable first"))
+                );
+            }
+
             AstExpression::Unary(Rc::new(RefCell::new(
                 AstUnary {
                     parent_data: parent_data.clone(),
@@ -391,7 +1415,7 @@ impl Parser {
                 }
             )))
         } else {
-            self.parse_primary_expression(parent_data.clone())
+            self.parse_postfix_expression(parent_data.clone())?
         };
 
         loop {
@@ -401,7 +1425,7 @@ impl Parser {
             }
 
             let operator = self.next_token();
-            let right = self.parse_binary_expression(precedence, parent_data.clone());
+            let right = self.parse_binary_expression(precedence, parent_data.clone())?;
             left = AstExpression::Binary(Rc::new(RefCell::new(
                 AstBinary {
                     parent_data: parent_data.clone(),
@@ -412,6 +1436,149 @@ impl Parser {
             )));
         }
 
-        left
+        Result::Ok(left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_statements(source: &str) -> Vec<Rc<RefCell<AstStatement>>> {
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file(String::from("test.lang"), String::from(source));
+        let ast = Parser::new(&source_map, file_id).parse().expect("source should parse");
+        let statements = match &*ast.borrow() {
+            Ast::File(file) => file.borrow().scope.borrow().statements.clone(),
+            Ast::Statement(statement) => vec![statement.clone()],
+        };
+        statements
+    }
+
+    /// Writes the expression with every operator application in parentheses
+    fn shape(expression: &AstExpression) -> String {
+        match expression {
+            AstExpression::Name(name) => String::from(name.borrow().token.identifier()),
+            AstExpression::Literal(literal) => match &literal.borrow().token.kind {
+                TokenKind::Integer(value, _) => value.to_string(),
+                kind => format!("{}", kind),
+            },
+            AstExpression::Field(field) => {
+                let field = field.borrow();
+                format!("{}.{}", shape(&field.operand.borrow()), field.name.identifier())
+            }
+            AstExpression::Index(index) => {
+                let index = index.borrow();
+                format!("{}[{}]", shape(&index.operand.borrow()), shape(&index.index.borrow()))
+            }
+            AstExpression::Unary(unary) => {
+                let unary = unary.borrow();
+                let operand = shape(&unary.operand.borrow());
+                if unary.operator.span.start > unary.operand.borrow().span().start {
+                    format!("({}{})", operand, unary.operator.kind)
+                } else {
+                    format!("({}{})", unary.operator.kind, operand)
+                }
+            }
+            AstExpression::Binary(binary) => {
+                let binary = binary.borrow();
+                format!("({} {} {})", shape(&binary.left.borrow()), binary.operator.kind, shape(&binary.right.borrow()))
+            }
+            _ => panic!("unexpected expression {:?}", expression),
+        }
+    }
+
+    fn declared_value(source: &str) -> String {
+        let statements = parse_statements(source);
+        let statement = statements.last().unwrap().borrow();
+        match &*statement {
+            AstStatement::Declaration(declaration) => shape(declaration.borrow().value.borrow().as_ref().unwrap()),
+            _ => panic!("expected a declaration"),
+        }
+    }
+
+    #[test]
+    fn each_bad_statement_is_reported() {
+        let source = "y := ;\nz := 1 +;\nw := 2 +;\nv := ;\nok := 1;\n";
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file(String::from("test.lang"), String::from(source));
+        let (_, diagnostics) = Parser::new(&source_map, file_id).parse_partial();
+
+        let reported: Vec<_> = diagnostics.iter()
+            .map(|diagnostic| (diagnostic.message.as_str(), source_map.line_column(diagnostic.span).0))
+            .collect();
+        assert_eq!(reported, [
+            ("Cannot have a declaration with neither type nor value", 1),
+            ("Expected expression got ';'", 2),
+            ("Expected expression got ';'", 3),
+            ("Cannot have a declaration with neither type nor value", 4),
+        ]);
+    }
+
+    #[test]
+    fn render_declaration_without_type_or_value() {
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file(String::from("test.lang"), String::from("y := ;\n"));
+        let (_, diagnostics) = Parser::new(&source_map, file_id).parse_partial();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].render(&source_map), concat!(
+            "error: Cannot have a declaration with neither type nor value\n",
+            " --> test.lang:1:1\n",
+            "  |\n",
+            "1 | y := ;\n",
+            "  | ^\n",
+            "  |\n",
+            "  = help: give it a value ('name := value;' or 'name: type = value;')\n",
+        ));
+    }
+
+    #[test]
+    fn dereference_before_binary_operator() {
+        assert_eq!(declared_value("b := p^ + 1;"), "((p'^') '+' 1)");
+        assert_eq!(declared_value("b := p^ - 1;"), "((p'^') '-' 1)");
+        assert_eq!(declared_value("b := p^ & 1;"), "((p'^') '&' 1)");
+    }
+
+    #[test]
+    fn dereference_before_xor() {
+        assert_eq!(declared_value("b := p^ ^ 2;"), "((p'^') '^' 2)");
+    }
+
+    #[test]
+    fn xor() {
+        assert_eq!(declared_value("c := a ^ b;"), "(a '^' b)");
+        assert_eq!(declared_value("c := a^b;"), "(a '^' b)");
+        assert_eq!(declared_value("c := a ^ (-b);"), "(a '^' ('-'b))");
+    }
+
+    #[test]
+    fn xor_with_prefix_operand() {
+        assert_eq!(declared_value("c := a ^ -b;"), "(a '^' ('-'b))");
+        assert_eq!(declared_value("c := a^-b;"), "(a '^' ('-'b))");
+        assert_eq!(declared_value("c := a ^ &b;"), "(a '^' ('&'b))");
+        assert_eq!(declared_value("c := a ^ ^b;"), "(a '^' ('^'b))");
+        assert_eq!(declared_value("c := a ^ !b;"), "(a '^' ('!'b))");
+        assert_eq!(declared_value("c := a ^ ~b;"), "(a '^' ('~'b))");
+    }
+
+    #[test]
+    fn chained_dereference() {
+        assert_eq!(declared_value("c := p^^ + 1;"), "(((p'^')'^') '+' 1)");
+        assert_eq!(declared_value("c := p^[0];"), "(p'^')[0]");
+    }
+
+    #[test]
+    fn assign_through_dereference() {
+        let statements = parse_statements("p^.x += 1;");
+        match &*statements[0].borrow() {
+            AstStatement::Assignment(assignment) => {
+                let assignment = assignment.borrow();
+                assert_eq!(shape(&assignment.left.borrow()), "(p'^').x");
+                assert_eq!(assignment.operator.kind, TokenKind::PlusEquals);
+                assert_eq!(shape(&assignment.right.borrow()), "1");
+            }
+            _ => panic!("expected an assignment"),
+        };
     }
 }
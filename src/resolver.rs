@@ -0,0 +1,441 @@
+pub use crate::ast::*;
+pub use crate::diagnostic::*;
+use std::collections::HashMap;
+
+/// Names that are always available without being declared
+pub const BUILTIN_NAMES: &[&str] = &[
+    "void", "bool", "int", "float",
+    "u8", "u16", "u32", "u64",
+    "i8", "i16", "i32", "i64",
+    "f32", "f64",
+    "string", "char",
+    "true", "false",
+];
+
+struct ResolverScope {
+    declarations: HashMap<String, Rc<RefCell<AstDeclaration>>>,
+    /// Set for the scope holding the parameters of a procedure,
+    /// locals of scopes outside of it can't be used from within the procedure
+    procedure: bool,
+}
+
+/// Binds every `AstName` to the `AstDeclaration` it refers to by walking the tree with a stack of scopes.
+/// Constants at file scope can be used anywhere in the file, everything else has to be declared before it is used.
+#[derive(Default)]
+pub struct Resolver {
+    scopes: Vec<ResolverScope>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver {
+            scopes: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, ast: &Rc<RefCell<Ast>>) -> Result<(), Vec<Diagnostic>> {
+        match &*ast.borrow() {
+            Ast::File(file) => self.resolve_file(&file.borrow()),
+            Ast::Statement(statement) => {
+                self.push_scope(false);
+                self.resolve_statement(&statement.borrow(), false);
+                self.scopes.pop();
+            }
+        }
+
+        let diagnostics = std::mem::take(&mut self.diagnostics);
+        if diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+            Result::Err(diagnostics)
+        } else {
+            Result::Ok(())
+        }
+    }
+
+    fn push_scope(&mut self, procedure: bool) {
+        self.scopes.push(ResolverScope {
+            declarations: HashMap::new(),
+            procedure,
+        });
+    }
+
+    fn declare(&mut self, declaration: &Rc<RefCell<AstDeclaration>>) {
+        let name = declaration.borrow().name.clone();
+        let scope = self.scopes.last_mut().expect("declarations are always inside a scope");
+
+        if let Option::Some(previous) = scope.declarations.get(name.identifier()) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("'{}' is already declared in this scope", name.identifier()), name.span)
+                    .with_label(previous.borrow().name.span, String::from("first declared here"))
+            );
+            return;
+        }
+        scope.declarations.insert(String::from(name.identifier()), declaration.clone());
+    }
+
+    fn resolve_name(&mut self, name: &Rc<RefCell<AstName>>) {
+        let token = name.borrow().token.clone();
+
+        let mut crossed_procedure = false;
+        for (index, scope) in self.scopes.iter().enumerate().rev() {
+            if let Option::Some(declaration) = scope.declarations.get(token.identifier()) {
+                // Procedures don't capture, only constants and file scope declarations are reachable from them
+                if crossed_procedure && index != 0 && !declaration.borrow().constant {
+                    self.diagnostics.push(
+                        Diagnostic::error(format!("Cannot use local '{}' of an enclosing procedure", token.identifier()), token.span)
+                            .with_label(declaration.borrow().name.span, String::from("declared here"))
+                    );
+                }
+                name.borrow_mut().declaration = Option::Some(Rc::downgrade(declaration));
+                return;
+            }
+            crossed_procedure |= scope.procedure;
+        }
+
+        if !BUILTIN_NAMES.contains(&token.identifier()) {
+            self.diagnostics.push(Diagnostic::error(format!("Undeclared name '{}'", token.identifier()), token.span));
+        }
+    }
+
+    fn resolve_file(&mut self, file: &AstFile) {
+        let scope = file.scope.borrow();
+        self.push_scope(false);
+
+        // File scope constants can be referenced before their definition
+        for statement in &scope.statements {
+            if let AstStatement::Declaration(declaration) = &*statement.borrow() {
+                if declaration.borrow().constant {
+                    self.declare(declaration);
+                }
+            }
+        }
+
+        for statement in &scope.statements {
+            self.resolve_statement(&statement.borrow(), true);
+        }
+        if let Option::Some(value) = &scope.value {
+            self.resolve_expression(&value.borrow());
+        }
+
+        self.scopes.pop();
+    }
+
+    /// Resolves the scope with `declarations` (e.g. the variable of a `for`) declared at its start
+    fn resolve_scope(&mut self, scope: &AstScope, declarations: &[Rc<RefCell<AstDeclaration>>]) {
+        self.push_scope(false);
+        for declaration in declarations {
+            self.declare(declaration);
+        }
+
+        for statement in &scope.statements {
+            self.resolve_statement(&statement.borrow(), false);
+        }
+        if let Option::Some(value) = &scope.value {
+            self.resolve_expression(&value.borrow());
+        }
+
+        self.scopes.pop();
+    }
+
+    fn resolve_declaration(&mut self, declaration: &Rc<RefCell<AstDeclaration>>, hoisted: bool) {
+        let (type_, value, constant) = {
+            let declaration = declaration.borrow();
+            (declaration.type_.clone(), declaration.value.clone(), declaration.constant)
+        };
+
+        if let Option::Some(type_) = &*type_.borrow() {
+            self.resolve_type(type_);
+        }
+
+        // Constants are declared before their value so that procedures can call themselves,
+        // variables only after it so that 'x := x;' refers to an outer 'x'
+        if constant && !hoisted {
+            self.declare(declaration);
+        }
+        if let Option::Some(value) = &*value.borrow() {
+            self.resolve_expression(value);
+        }
+        if !constant {
+            self.declare(declaration);
+        }
+    }
+
+    fn resolve_statement(&mut self, statement: &AstStatement, file_scope: bool) {
+        match statement {
+            AstStatement::Expression(expression) => self.resolve_expression(&expression.borrow()),
+            AstStatement::Scope(scope) => self.resolve_scope(&scope.borrow(), &[]),
+            AstStatement::Declaration(declaration) => {
+                let hoisted = file_scope && declaration.borrow().constant;
+                self.resolve_declaration(declaration, hoisted);
+            }
+            AstStatement::Assignment(assignment) => {
+                let assignment = assignment.borrow();
+                self.resolve_expression(&assignment.left.borrow());
+                self.resolve_expression(&assignment.right.borrow());
+            }
+            AstStatement::If(if_) => self.resolve_if(&if_.borrow()),
+            AstStatement::Match(match_) => self.resolve_match(&match_.borrow()),
+            AstStatement::While(while_) => {
+                let while_ = while_.borrow();
+                self.resolve_expression(&while_.condition.borrow());
+                self.resolve_scope(&while_.body.borrow(), &[]);
+            }
+            AstStatement::Loop(loop_) => self.resolve_scope(&loop_.borrow().body.borrow(), &[]),
+            AstStatement::For(for_) => {
+                let for_ = for_.borrow();
+                self.resolve_expression(&for_.start.borrow());
+                self.resolve_expression(&for_.end.borrow());
+                self.resolve_scope(&for_.body.borrow(), std::slice::from_ref(&for_.variable));
+            }
+            AstStatement::Return(return_) => {
+                if let Option::Some(value) = &return_.borrow().value {
+                    self.resolve_expression(&value.borrow());
+                }
+            }
+            AstStatement::Break(_) |
+            AstStatement::Continue(_) |
+            AstStatement::Error => {}
+        }
+    }
+
+    fn resolve_if(&mut self, if_: &AstIf) {
+        self.resolve_expression(&if_.condition.borrow());
+        self.resolve_scope(&if_.then_scope.borrow(), &[]);
+        match &if_.else_ {
+            Option::Some(AstElse::If(else_if)) => self.resolve_if(&else_if.borrow()),
+            Option::Some(AstElse::Scope(scope)) => self.resolve_scope(&scope.borrow(), &[]),
+            Option::None => {}
+        }
+    }
+
+    fn resolve_match(&mut self, match_: &AstMatch) {
+        self.resolve_expression(&match_.value.borrow());
+        for arm in &match_.arms {
+            let bindings: Vec<_> = match &arm.pattern {
+                AstPattern::Variant(variant) => variant.borrow().bindings.iter().flatten().cloned().collect(),
+                AstPattern::Wildcard(_) |
+                AstPattern::Literal(_) => Vec::new(),
+            };
+            self.resolve_scope(&arm.body.borrow(), &bindings);
+        }
+    }
+
+    fn resolve_procedure(&mut self, procedure: &AstProcedure) {
+        self.push_scope(true);
+
+        for argument in &procedure.arguments {
+            self.resolve_declaration(argument, false);
+        }
+        if let Option::Some(return_type) = &*procedure.return_type.borrow() {
+            self.resolve_type(return_type);
+        }
+        self.resolve_scope(&procedure.scope.borrow(), &[]);
+
+        self.scopes.pop();
+    }
+
+    fn resolve_expression(&mut self, expression: &AstExpression) {
+        match expression {
+            AstExpression::Procedure(procedure) => self.resolve_procedure(&procedure.borrow()),
+            AstExpression::Call(call) => {
+                let call = call.borrow();
+                self.resolve_expression(&call.callee.borrow());
+                for argument in &call.arguments {
+                    self.resolve_expression(&argument.value.borrow());
+                }
+            }
+            AstExpression::Struct(struct_) => {
+                for field in &struct_.borrow().fields {
+                    let field = field.borrow();
+                    if let Option::Some(type_) = &*field.type_.borrow() {
+                        self.resolve_type(type_);
+                    }
+                    if let Option::Some(value) = &*field.value.borrow() {
+                        self.resolve_expression(value);
+                    };
+                }
+            }
+            AstExpression::StructLiteral(literal) => {
+                let literal = literal.borrow();
+                self.resolve_type(&literal.type_.borrow());
+                for field in &literal.fields {
+                    self.resolve_expression(&field.value.borrow());
+                }
+            }
+            AstExpression::Field(field) => self.resolve_expression(&field.borrow().operand.borrow()),
+            AstExpression::ArrayLiteral(array) => {
+                for element in &array.borrow().elements {
+                    self.resolve_expression(&element.borrow());
+                }
+            }
+            AstExpression::Index(index) => {
+                let index = index.borrow();
+                self.resolve_expression(&index.operand.borrow());
+                self.resolve_expression(&index.index.borrow());
+            }
+            AstExpression::Slice(slice) => {
+                let slice = slice.borrow();
+                self.resolve_expression(&slice.operand.borrow());
+                for bound in slice.start.iter().chain(&slice.end) {
+                    self.resolve_expression(&bound.borrow());
+                }
+            }
+            AstExpression::Enum(enum_) => {
+                for variant in &enum_.borrow().variants {
+                    for type_ in &variant.payload {
+                        self.resolve_type(&type_.borrow());
+                    }
+                }
+            }
+            AstExpression::If(if_) => self.resolve_if(&if_.borrow()),
+            AstExpression::Match(match_) => self.resolve_match(&match_.borrow()),
+            AstExpression::Name(name) => self.resolve_name(name),
+            AstExpression::Literal(_) => {}
+            AstExpression::Unary(unary) => self.resolve_expression(&unary.borrow().operand.borrow()),
+            AstExpression::Binary(binary) => {
+                let binary = binary.borrow();
+                self.resolve_expression(&binary.left.borrow());
+                self.resolve_expression(&binary.right.borrow());
+            }
+            AstExpression::Error(_) => {}
+        }
+    }
+
+    fn resolve_type(&mut self, type_: &AstType) {
+        match type_ {
+            AstType::Name(name) => self.resolve_name(name),
+            AstType::Pointer(pointer) => self.resolve_type(&pointer.borrow().pointee.borrow()),
+            AstType::Array(array) => {
+                let array = array.borrow();
+                self.resolve_expression(&array.length.borrow());
+                self.resolve_type(&array.element.borrow());
+            }
+            AstType::Slice(slice) => self.resolve_type(&slice.borrow().element.borrow()),
+            AstType::Procedure(procedure) => {
+                let procedure = procedure.borrow();
+                for parameter in &procedure.parameters {
+                    self.resolve_type(&parameter.borrow());
+                }
+                if let Option::Some(return_type) = &*procedure.return_type.borrow() {
+                    self.resolve_type(return_type);
+                };
+            }
+            AstType::Optional(optional) => self.resolve_type(&optional.borrow().inner.borrow()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::*;
+
+    fn parse(source: &str) -> Rc<RefCell<Ast>> {
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file(String::from("test.lang"), String::from(source));
+        Parser::new(&source_map, file_id).parse().expect("source should parse")
+    }
+
+    /// The messages of the errors reported while resolving `source`
+    fn errors(source: &str) -> Vec<String> {
+        match Resolver::new().resolve(&parse(source)) {
+            Result::Ok(()) => Vec::new(),
+            Result::Err(diagnostics) => diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect(),
+        }
+    }
+
+    #[test]
+    fn resolve_single_statement() {
+        let ast = parse("x := 1;");
+        let statement = match &*ast.borrow() {
+            Ast::File(file) => file.borrow().scope.borrow().statements[0].clone(),
+            Ast::Statement(statement) => statement.clone(),
+        };
+
+        let ast = Rc::new(RefCell::new(Ast::Statement(statement)));
+        assert!(Resolver::new().resolve(&ast).is_ok());
+    }
+
+    #[test]
+    fn normalized_names_resolve() {
+        assert_eq!(errors("caf\u{E9} := 1; x := cafe\u{301};"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn undeclared_name() {
+        assert_eq!(errors("x := y;"), ["Undeclared name 'y'"]);
+        assert_eq!(errors("x: Foo = 1;"), ["Undeclared name 'Foo'"]);
+        assert_eq!(errors("x: int = 1; y := true;"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn duplicate_declaration() {
+        assert_eq!(errors("x := 1; x := 2;"), ["'x' is already declared in this scope"]);
+        assert_eq!(errors("f :: (a: int, a: int) {}"), ["'a' is already declared in this scope"]);
+        // Shadowing in a nested scope is fine
+        assert_eq!(errors("x := 1; { x := 2; }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn forward_reference_to_file_constant() {
+        let ast = parse("main :: () { y := X; } X :: 5;");
+        assert!(Resolver::new().resolve(&ast).is_ok());
+
+        // The 'X' in 'main' is bound to the declaration after it
+        let statements = match &*ast.borrow() {
+            Ast::File(file) => file.borrow().scope.borrow().statements.clone(),
+            Ast::Statement(_) => unreachable!(),
+        };
+        let main = match &*statements[0].borrow() {
+            AstStatement::Declaration(main) => main.clone(),
+            _ => panic!("expected a declaration"),
+        };
+        let constant = match &*statements[1].borrow() {
+            AstStatement::Declaration(constant) => constant.clone(),
+            _ => panic!("expected a declaration"),
+        };
+        let main = main.borrow();
+        let procedure = match &*main.value.borrow() {
+            Option::Some(AstExpression::Procedure(procedure)) => procedure.clone(),
+            _ => panic!("expected a procedure"),
+        };
+        let procedure = procedure.borrow();
+        let scope = procedure.scope.borrow();
+        let y = match &*scope.statements[0].borrow() {
+            AstStatement::Declaration(y) => y.clone(),
+            _ => panic!("expected a declaration"),
+        };
+        let y = y.borrow();
+        let value = y.value.borrow();
+        match value.as_ref() {
+            Option::Some(AstExpression::Name(name)) => {
+                let declaration = name.borrow().declaration.as_ref().and_then(Weak::upgrade).expect("'X' should be resolved");
+                assert!(Rc::ptr_eq(&declaration, &constant));
+            }
+            _ => panic!("expected a name"),
+        };
+    }
+
+    #[test]
+    fn local_used_before_declaration() {
+        assert_eq!(errors("main :: () { y := x; x := 1; }"), ["Undeclared name 'x'"]);
+        // Only file scope constants are hoisted
+        assert_eq!(errors("main :: () { y := X; X :: 1; }"), ["Undeclared name 'X'"]);
+        assert_eq!(errors("x := x;"), ["Undeclared name 'x'"]);
+    }
+
+    #[test]
+    fn local_of_enclosing_procedure() {
+        assert_eq!(
+            errors("main :: () { x := 1; inner :: () { y := x; } }"),
+            ["Cannot use local 'x' of an enclosing procedure"],
+        );
+        assert_eq!(
+            errors("main :: (a: int) { inner :: () { y := a; } }"),
+            ["Cannot use local 'a' of an enclosing procedure"],
+        );
+        // Constants and file scope declarations are still reachable
+        assert_eq!(errors("g := 1; main :: () { C :: 2; inner :: () { y := g + C; } }"), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,164 @@
+use std::ops::Range;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FileId(pub usize);
+
+/// A range of bytes `start..end` in one of the files of a `SourceMap`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Span {
+    pub file_id: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file_id: FileId, start: usize, end: usize) -> Span {
+        Span {
+            file_id,
+            start,
+            end,
+        }
+    }
+
+    /// The smallest span covering both `self` and `other`, which must be in the same file
+    pub fn to(self, other: Span) -> Span {
+        debug_assert_eq!(self.file_id, other.file_id);
+        Span::new(self.file_id, self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn range(self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SourceFile {
+    pub path: String,
+    pub source: String,
+    /// Byte offset of the start of every line
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(path: String, source: String) -> SourceFile {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+
+        SourceFile {
+            path,
+            source,
+            line_starts,
+        }
+    }
+
+    /// The 1-based line and column (in characters) of a byte offset
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Result::Ok(line) => line,
+            Result::Err(line) => line - 1,
+        };
+        let line_start = self.line_starts[line];
+        let column = self.source[line_start..offset.min(self.source.len())].chars().count();
+        (line + 1, column + 1)
+    }
+
+    /// The text of a 1-based line without its line ending
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts.get(line - 1).copied().unwrap_or(self.source.len());
+        let end = self.line_starts.get(line).copied().unwrap_or(self.source.len());
+        self.source[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+/// Owns every loaded source file so spans from any of them can be mapped back to text
+#[derive(Default, Debug)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap {
+            files: Vec::new(),
+        }
+    }
+
+    pub fn add_file(&mut self, path: String, source: String) -> FileId {
+        self.files.push(SourceFile::new(path, source));
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn load(&mut self, path: &str) -> std::io::Result<FileId> {
+        let source = std::fs::read_to_string(path)?;
+        Result::Ok(self.add_file(String::from(path), source))
+    }
+
+    pub fn file(&self, file_id: FileId) -> &SourceFile {
+        &self.files[file_id.0]
+    }
+
+    /// The 1-based line and column of the start of a span
+    pub fn line_column(&self, span: Span) -> (usize, usize) {
+        self.file(span.file_id).line_column(span.start)
+    }
+
+    pub fn text(&self, span: Span) -> &str {
+        &self.file(span.file_id).source[span.range()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_column_at_line_edges() {
+        let file = SourceFile::new(String::from("test.lang"), String::from("ab\ncd\n\nef"));
+        assert_eq!(file.line_column(0), (1, 1));
+        // The '\n' is the last byte of its line
+        assert_eq!(file.line_column(2), (1, 3));
+        assert_eq!(file.line_column(3), (2, 1));
+        assert_eq!(file.line_column(4), (2, 2));
+        assert_eq!(file.line_column(6), (3, 1));
+        assert_eq!(file.line_column(8), (4, 2));
+        // The end of the file
+        assert_eq!(file.line_column(9), (4, 3));
+    }
+
+    #[test]
+    fn column_counts_characters() {
+        // 'é' is 2 bytes and '\u{3C0}' is 2 bytes
+        let file = SourceFile::new(String::from("test.lang"), String::from("x\né\u{3C0} = 1"));
+        assert_eq!(file.line_column(2), (2, 1));
+        assert_eq!(file.line_column(4), (2, 2));
+        assert_eq!(file.line_column(6), (2, 3));
+        assert_eq!(file.line_column(7), (2, 4));
+    }
+
+    #[test]
+    fn lines_without_line_endings() {
+        let file = SourceFile::new(String::from("test.lang"), String::from("ab\r\ncd\n\nef"));
+        assert_eq!(file.line(1), "ab");
+        assert_eq!(file.line(2), "cd");
+        assert_eq!(file.line(3), "");
+        assert_eq!(file.line(4), "ef");
+        assert_eq!(file.line(5), "");
+    }
+
+    #[test]
+    fn spans_from_several_files() {
+        let mut source_map = SourceMap::new();
+        let first = source_map.add_file(String::from("a.lang"), String::from("first\nline"));
+        let second = source_map.add_file(String::from("b.lang"), String::from("second"));
+        assert_ne!(first, second);
+
+        let in_first = Span::new(first, 6, 10);
+        let in_second = Span::new(second, 0, 3);
+        assert_eq!(source_map.text(in_first), "line");
+        assert_eq!(source_map.line_column(in_first), (2, 1));
+        assert_eq!(source_map.text(in_second), "sec");
+        assert_eq!(source_map.line_column(in_second), (1, 1));
+        assert_eq!(source_map.file(second).path, "b.lang");
+    }
+}
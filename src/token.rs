@@ -1,11 +1,29 @@
+pub use crate::source_map::*;
+use std::fmt;
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum TokenKind {
     EndOfFile,
 
     Error(String),
+    DocComment(String),
     Identifier(String),
-    Integer(u64),
-    Float(f64),
+    If,
+    Else,
+    Struct,
+    Enum,
+    Match,
+    While,
+    Loop,
+    For,
+    In,
+    Break,
+    Continue,
+    Return,
+    Integer(u64, Option<NumberSuffix>),
+    Float(f64, Option<NumberSuffix>),
+    String(String),
+    Char(char),
 
     Colon,
     Semicolon,
@@ -13,8 +31,14 @@ pub enum TokenKind {
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
     Comma,
+    Dot,
+    QuestionMark,
     RightArrow,
+    FatArrow,
+    DotDot,
 
     Plus,
     Minus,
@@ -23,33 +47,210 @@ pub enum TokenKind {
     Percent,
     Equals,
     ExclamationMark,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    LessThanLessThan,
+    GreaterThanGreaterThan,
 
     PlusEquals,
     MinusEquals,
     AsteriskEquals,
     SlashEquals,
     PercentEquals,
+    AmpersandEquals,
+    PipeEquals,
+    CaretEquals,
+    LessThanLessThanEquals,
+    GreaterThanGreaterThanEquals,
     EqualsEquals,
     ExclamationMarkEquals,
+    LessThan,
+    LessThanEquals,
+    GreaterThan,
+    GreaterThanEquals,
+    AmpersandAmpersand,
+    PipePipe,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::EndOfFile => write!(f, "end of file"),
+
+            TokenKind::Error(message) => write!(f, "invalid token ({})", message),
+            TokenKind::DocComment(_) => write!(f, "doc comment"),
+            TokenKind::Identifier(name) => write!(f, "identifier '{}'", name),
+            TokenKind::If => write!(f, "'if'"),
+            TokenKind::Else => write!(f, "'else'"),
+            TokenKind::Struct => write!(f, "'struct'"),
+            TokenKind::Enum => write!(f, "'enum'"),
+            TokenKind::Match => write!(f, "'match'"),
+            TokenKind::While => write!(f, "'while'"),
+            TokenKind::Loop => write!(f, "'loop'"),
+            TokenKind::For => write!(f, "'for'"),
+            TokenKind::In => write!(f, "'in'"),
+            TokenKind::Break => write!(f, "'break'"),
+            TokenKind::Continue => write!(f, "'continue'"),
+            TokenKind::Return => write!(f, "'return'"),
+            TokenKind::Integer(value, Option::Some(suffix)) => write!(f, "integer '{}{}'", value, suffix),
+            TokenKind::Integer(value, Option::None) => write!(f, "integer '{}'", value),
+            TokenKind::Float(value, Option::Some(suffix)) => write!(f, "float '{}{}'", value, suffix),
+            TokenKind::Float(value, Option::None) => write!(f, "float '{}'", value),
+            TokenKind::String(value) => write!(f, "string {:?}", value),
+            TokenKind::Char(value) => write!(f, "character {:?}", value),
+
+            TokenKind::Colon => write!(f, "':'"),
+            TokenKind::Semicolon => write!(f, "';'"),
+            TokenKind::LParen => write!(f, "'('"),
+            TokenKind::RParen => write!(f, "')'"),
+            TokenKind::LBrace => write!(f, "'{{'"),
+            TokenKind::RBrace => write!(f, "'}}'"),
+            TokenKind::LBracket => write!(f, "'['"),
+            TokenKind::RBracket => write!(f, "']'"),
+            TokenKind::Comma => write!(f, "','"),
+            TokenKind::Dot => write!(f, "'.'"),
+            TokenKind::QuestionMark => write!(f, "'?'"),
+            TokenKind::RightArrow => write!(f, "'->'"),
+            TokenKind::FatArrow => write!(f, "'=>'"),
+            TokenKind::DotDot => write!(f, "'..'"),
+
+            TokenKind::Plus => write!(f, "'+'"),
+            TokenKind::Minus => write!(f, "'-'"),
+            TokenKind::Asterisk => write!(f, "'*'"),
+            TokenKind::Slash => write!(f, "'/'"),
+            TokenKind::Percent => write!(f, "'%'"),
+            TokenKind::Equals => write!(f, "'='"),
+            TokenKind::ExclamationMark => write!(f, "'!'"),
+            TokenKind::Ampersand => write!(f, "'&'"),
+            TokenKind::Pipe => write!(f, "'|'"),
+            TokenKind::Caret => write!(f, "'^'"),
+            TokenKind::Tilde => write!(f, "'~'"),
+            TokenKind::LessThanLessThan => write!(f, "'<<'"),
+            TokenKind::GreaterThanGreaterThan => write!(f, "'>>'"),
+
+            TokenKind::PlusEquals => write!(f, "'+='"),
+            TokenKind::MinusEquals => write!(f, "'-='"),
+            TokenKind::AsteriskEquals => write!(f, "'*='"),
+            TokenKind::SlashEquals => write!(f, "'/='"),
+            TokenKind::PercentEquals => write!(f, "'%='"),
+            TokenKind::AmpersandEquals => write!(f, "'&='"),
+            TokenKind::PipeEquals => write!(f, "'|='"),
+            TokenKind::CaretEquals => write!(f, "'^='"),
+            TokenKind::LessThanLessThanEquals => write!(f, "'<<='"),
+            TokenKind::GreaterThanGreaterThanEquals => write!(f, "'>>='"),
+            TokenKind::EqualsEquals => write!(f, "'=='"),
+            TokenKind::ExclamationMarkEquals => write!(f, "'!='"),
+            TokenKind::LessThan => write!(f, "'<'"),
+            TokenKind::LessThanEquals => write!(f, "'<='"),
+            TokenKind::GreaterThan => write!(f, "'>'"),
+            TokenKind::GreaterThanEquals => write!(f, "'>='"),
+            TokenKind::AmpersandAmpersand => write!(f, "'&&'"),
+            TokenKind::PipePipe => write!(f, "'||'"),
+        }
+    }
+}
+
+/// The explicit type written after a number literal, e.g. the `u8` in `10u8`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NumberSuffix {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl NumberSuffix {
+    pub fn from_name(name: &str) -> Option<NumberSuffix> {
+        match name {
+            "u8" => Option::Some(NumberSuffix::U8),
+            "u16" => Option::Some(NumberSuffix::U16),
+            "u32" => Option::Some(NumberSuffix::U32),
+            "u64" => Option::Some(NumberSuffix::U64),
+            "i8" => Option::Some(NumberSuffix::I8),
+            "i16" => Option::Some(NumberSuffix::I16),
+            "i32" => Option::Some(NumberSuffix::I32),
+            "i64" => Option::Some(NumberSuffix::I64),
+            "f32" => Option::Some(NumberSuffix::F32),
+            "f64" => Option::Some(NumberSuffix::F64),
+            _ => Option::None,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, NumberSuffix::F32 | NumberSuffix::F64)
+    }
+
+    pub fn is_signed(self) -> bool {
+        !matches!(self, NumberSuffix::U8 | NumberSuffix::U16 | NumberSuffix::U32 | NumberSuffix::U64)
+    }
+
+    /// The largest integer literal of this type, a `negated` literal of a signed type
+    /// can be one more than the maximum so that the minimum can be written, e.g. '-128' for i8
+    pub fn max_literal(self, negated: bool) -> u64 {
+        let max = match self {
+            NumberSuffix::U8 => u8::MAX as u64,
+            NumberSuffix::U16 => u16::MAX as u64,
+            NumberSuffix::U32 => u32::MAX as u64,
+            NumberSuffix::U64 => u64::MAX,
+            NumberSuffix::I8 => i8::MAX as u64,
+            NumberSuffix::I16 => i16::MAX as u64,
+            NumberSuffix::I32 => i32::MAX as u64,
+            NumberSuffix::I64 => i64::MAX as u64,
+            NumberSuffix::F32 | NumberSuffix::F64 => u64::MAX,
+        };
+        if negated && self.is_signed() && !self.is_float() {
+            max + 1
+        } else {
+            max
+        }
+    }
+}
+
+impl fmt::Display for NumberSuffix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumberSuffix::U8 => write!(f, "u8"),
+            NumberSuffix::U16 => write!(f, "u16"),
+            NumberSuffix::U32 => write!(f, "u32"),
+            NumberSuffix::U64 => write!(f, "u64"),
+            NumberSuffix::I8 => write!(f, "i8"),
+            NumberSuffix::I16 => write!(f, "i16"),
+            NumberSuffix::I32 => write!(f, "i32"),
+            NumberSuffix::I64 => write!(f, "i64"),
+            NumberSuffix::F32 => write!(f, "f32"),
+            NumberSuffix::F64 => write!(f, "f64"),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Token {
     pub kind: TokenKind,
-    pub position: usize,
-    pub line: usize,
-    pub column: usize,
-    pub length: usize,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(kind: TokenKind, position: usize, line: usize, column: usize, length: usize) -> Token {
+    pub fn new(kind: TokenKind, span: Span) -> Token {
         Token {
             kind,
-            position,
-            line,
-            column,
-            length,
+            span,
+        }
+    }
+
+    /// The name of an identifier token, empty for any other kind of token
+    pub fn identifier(&self) -> &str {
+        if let TokenKind::Identifier(name) = &self.kind {
+            name
+        } else {
+            ""
         }
     }
 }